@@ -0,0 +1,65 @@
+use core::borrow::Borrow;
+use core::cmp::Ordering;
+
+use crate::{AbstractOrd, QWrapper};
+
+// A wrapper which reverses the ordering of the wrapped value, so that a list,
+// map or set of Descending values is ordered from greatest to least.
+//
+// Descending<T> can only be borrowed as itself, so a Map or Set keyed by it is
+// looked up by Descending<T>. A raw SkipList can also be looked up by any type
+// T borrows as, using Descending::borrowed.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[repr(transparent)]
+pub struct Descending<T: ?Sized>(pub T);
+
+impl<Q: ?Sized> Descending<QWrapper<Q>> {
+    pub fn borrowed(key: &Q) -> &Descending<QWrapper<Q>> {
+        // SAFETY: Descending and QWrapper are both repr(transparent), so
+        // Descending<QWrapper<Q>> has the same layout as Q, and a reference to
+        // Q, including the metadata of an unsized Q, is a valid reference to
+        // it with the same lifetime. Neither wrapper adds invariants of its own.
+        unsafe { &*(key as *const Q as *const Descending<QWrapper<Q>>) }
+    }
+}
+
+impl<T: Ord + ?Sized> PartialOrd for Descending<T> {
+    fn partial_cmp(&self, rhs: &Descending<T>) -> Option<Ordering> {
+        Some(Ord::cmp(self, rhs))
+    }
+}
+
+impl<T: Ord + ?Sized> Ord for Descending<T> {
+    fn cmp(&self, rhs: &Descending<T>) -> Ordering {
+        Ord::cmp(&rhs.0, &self.0)
+    }
+}
+
+impl<T, Q> AbstractOrd<Descending<T>> for Descending<QWrapper<Q>> where
+    T: Ord + Borrow<Q>,
+    Q: Ord + ?Sized,
+{
+    fn cmp(&self, rhs: &Descending<T>) -> Ordering {
+        Ord::cmp(rhs.0.borrow(), &self.0.0)
+    }
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn test_descending() {
+    use crate::{Map, Set, SkipList};
+
+    let list: SkipList<Descending<String>> = ["a", "c", "b"].iter().map(|s| Descending(s.to_string())).collect();
+    assert!(list.elems().map(|s| &s.0[..]).eq(["c", "b", "a"]));
+    assert_eq!(list.get(Descending::borrowed("b")).map(|s| &s.0[..]), Some("b"));
+    assert_eq!(list.get(Descending::borrowed("d")), None);
+    assert_eq!(list.get_ge(Descending::borrowed("bb")).map(|s| &s.0[..]), Some("b"));
+
+    let set: Set<_> = (0..10).map(Descending).collect();
+    assert!(set.iter().map(|x| x.0).eq((0..10).rev()));
+    assert_eq!(set.first(), Some(&Descending(9)));
+
+    let map: Map<_, _> = (0..10).map(|x| (Descending(x), x * 2)).collect();
+    assert_eq!(map.get(&Descending(4)), Some(&8));
+    assert_eq!(map.first_key_value(), Some((&Descending(9), &18)));
+}
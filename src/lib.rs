@@ -1,6 +1,18 @@
-#![feature(manually_drop_take, atomic_min_max)]
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+
+mod descending;
 mod ord;
 mod skiplist;
+mod sync;
+
+#[cfg(feature = "rayon")]
+mod rayon;
+#[cfg(feature = "serde")]
+mod serde;
+#[cfg(feature = "proptest")]
+mod proptest;
 
 pub mod map;
 pub mod set;
@@ -9,8 +21,14 @@ use ord::{AbstractOrd, QWrapper};
 use skiplist::SkipList;
 
 pub mod raw {
-    pub use crate::skiplist::SkipList;
+    pub use crate::ord::{AbstractOrd, QWrapper};
+    pub use crate::skiplist::{Allocator, Cursor, Finger, Global, SkipList, SkipListBy};
+    #[cfg(feature = "std")]
+    pub use crate::skiplist::Recycling;
+    #[cfg(feature = "metrics")]
+    pub use crate::skiplist::SkipListStats;
 }
 
+pub use descending::Descending;
 pub use map::Map;
 pub use set::Set;
@@ -1,22 +1,102 @@
-use std::borrow::Borrow;
-use std::cmp::Ordering;
-use std::iter::FromIterator;
+use alloc::collections::BTreeMap;
+use core::borrow::Borrow;
+use core::cmp::Ordering;
+use core::fmt;
+use core::hash::{Hash, Hasher};
+use core::iter::{FromIterator, FusedIterator};
+use core::ops::{Index, RangeBounds};
 
 use crate::{SkipList, AbstractOrd, QWrapper};
+use crate::ord::QRange;
+use crate::skiplist::{self, Elems, IntoElems, RawExtract, MAX_HEIGHT};
 
-pub struct Map<K, V> {
-    inner: SkipList<KeyValue<K, V>>,
+#[derive(Clone)]
+pub struct Map<K, V, const H: usize = MAX_HEIGHT> {
+    inner: SkipList<KeyValue<K, V>, H>,
 }
 
 impl<K: Ord, V> Map<K, V> {
     pub fn new() -> Map<K, V> {
         Map { inner: SkipList::new() }
     }
+}
+
+impl<K: Ord, V, const H: usize> Map<K, V, H> {
+    // Construct a map whose list has at most `H` lanes; see SkipList.
+    pub fn with_height() -> Map<K, V, H> {
+        Map { inner: SkipList::with_height() }
+    }
+
+    // Construct a map expecting to hold about `capacity` entries; see SkipList.
+    pub fn with_capacity(capacity: usize) -> Map<K, V, H> {
+        Map { inner: SkipList::with_capacity(capacity) }
+    }
+
+    // Construct a map which draws node heights from `source`; see SkipList.
+    pub fn with_height_source(source: fn() -> u64) -> Map<K, V, H> {
+        Map { inner: SkipList::with_height_source(source) }
+    }
 
     pub fn insert(&self, key: K, value: V) -> Option<(K, V, &K, &V)> {
         self.inner.insert(KeyValue(key, value)).map(|(KeyValue(k, v), kv)| (k, v, &kv.0, &kv.1))
     }
 
+    // The value is only constructed if the key is not already present. If
+    // another thread inserts the same key concurrently, the value constructed
+    // here is dropped and the value that thread inserted is returned.
+    pub fn get_or_insert_with<F: FnOnce() -> V>(&self, key: K, f: F) -> &V {
+        self.entry(key).or_insert_with(f)
+    }
+
+    pub fn entry(&self, key: K) -> Entry<'_, K, V, H> {
+        match self.get_key_value(&key) {
+            Some((key, value))  => Entry::Occupied(OccupiedEntry { key, value }),
+            None                => Entry::Vacant(VacantEntry { key, map: self }),
+        }
+    }
+
+    // If the key is present, calls `f` with the key and its value, replacing
+    // the value with the value `f` returns, or removing the entry if it returns
+    // None. This takes exclusive access to the map, so no concurrent get can
+    // observe the entry while it is being updated: every get before this call
+    // sees the old value and every get after it sees the new one.
+    pub fn compute_if_present<Q, F>(&mut self, key: &Q, f: F) -> Option<&V>
+    where
+        Q: Ord + ?Sized,
+        K: Borrow<Q>,
+        F: FnOnce(&K, V) -> Option<V>,
+    {
+        let KeyValue(key, value) = self.inner.take(QWrapper::new(key))?;
+        let value = f(&key, value)?;
+        Some(&self.inner.get_or_insert(KeyValue(key, value)).1)
+    }
+
+    // Calls `f` with the value of `key`, returning whether the key was present.
+    // Values with interior mutability, such as atomics or mutexes, can be
+    // updated this way through a shared reference, while other threads insert
+    // and update other keys.
+    pub fn update<Q, F>(&self, key: &Q, f: F) -> bool
+    where
+        Q: Ord + ?Sized,
+        K: Borrow<Q>,
+        F: FnOnce(&V),
+    {
+        self.with_mut(key, f).is_some()
+    }
+
+    // Like update, but returns the result of `f`, if the key was present. The
+    // value is only ever shared, even while `f` mutates it, so it must be of a
+    // type which can be mutated safely from several threads at once, such as
+    // an atomic or a Mutex; a Cell would make the map no longer Sync.
+    pub fn with_mut<Q, R, F>(&self, key: &Q, f: F) -> Option<R>
+    where
+        Q: Ord + ?Sized,
+        K: Borrow<Q>,
+        F: FnOnce(&V) -> R,
+    {
+        self.get(key).map(f)
+    }
+
     pub fn contains<Q>(&self, key: &Q) -> bool
     where
         Q: Ord + ?Sized,
@@ -33,6 +113,33 @@ impl<K: Ord, V> Map<K, V> {
         self.inner.get(QWrapper::new(key)).map(|KeyValue(_, v)| v)
     }
 
+    pub fn get_mut<Q>(&mut self, key: &Q) -> Option<&mut V>
+    where
+        Q: Ord + ?Sized,
+        K: Borrow<Q>,
+    {
+        self.inner.get_mut(QWrapper::new(key)).map(|KeyValue(_, v)| v)
+    }
+
+    pub fn get_key_value_mut<Q>(&mut self, key: &Q) -> Option<(&K, &mut V)>
+    where
+        Q: Ord + ?Sized,
+        K: Borrow<Q>,
+    {
+        self.inner.get_mut(QWrapper::new(key)).map(|KeyValue(k, v)| (&*k, v))
+    }
+
+    // Returns mutable references to the values of several keys at once, or
+    // None if any key is missing or any two keys are equal.
+    pub fn get_disjoint_mut<Q, const N: usize>(&mut self, keys: [&Q; N]) -> Option<[&mut V; N]>
+    where
+        Q: Ord + ?Sized,
+        K: Borrow<Q>,
+    {
+        let entries = self.inner.get_disjoint_mut(keys.map(QWrapper::new))?;
+        Some(entries.map(|KeyValue(_, v)| v))
+    }
+
     pub fn get_key_value<Q>(&self, key: &Q) -> Option<(&K, &V)>
     where
         Q: Ord + ?Sized,
@@ -40,8 +147,217 @@ impl<K: Ord, V> Map<K, V> {
     {
         self.inner.get(QWrapper::new(key)).map(|KeyValue(k, v)| (k, v))
     }
+
+    pub fn first_key_value(&self) -> Option<(&K, &V)> {
+        self.inner.first().map(|KeyValue(k, v)| (k, v))
+    }
+
+    pub fn last_key_value(&self) -> Option<(&K, &V)> {
+        self.inner.last().map(|KeyValue(k, v)| (k, v))
+    }
+
+    pub fn floor_key_value<Q>(&self, key: &Q) -> Option<(&K, &V)>
+    where
+        Q: Ord + ?Sized,
+        K: Borrow<Q>,
+    {
+        self.inner.get_le(QWrapper::new(key)).map(|KeyValue(k, v)| (k, v))
+    }
+
+    pub fn ceiling_key_value<Q>(&self, key: &Q) -> Option<(&K, &V)>
+    where
+        Q: Ord + ?Sized,
+        K: Borrow<Q>,
+    {
+        self.inner.get_ge(QWrapper::new(key)).map(|KeyValue(k, v)| (k, v))
+    }
+
+    // Entries removed from the map are not deallocated while the map is
+    // shared, so they are returned by reference rather than by value.
+    pub fn remove_first(&self) -> Option<(&K, &V)> {
+        self.inner.remove_first().map(|KeyValue(k, v)| (k, v))
+    }
+
+    pub fn remove_last(&self) -> Option<(&K, &V)> {
+        self.inner.remove_last().map(|KeyValue(k, v)| (k, v))
+    }
+
+    pub fn pop_first(&mut self) -> Option<(K, V)> {
+        self.inner.pop_first().map(|KeyValue(k, v)| (k, v))
+    }
+
+    pub fn pop_last(&mut self) -> Option<(K, V)> {
+        self.inner.pop_last().map(|KeyValue(k, v)| (k, v))
+    }
+
+    pub fn range_count<Q, R>(&self, range: R) -> usize
+    where
+        Q: Ord + ?Sized,
+        K: Borrow<Q>,
+        R: RangeBounds<Q>,
+    {
+        self.inner.range_count(QRange(range))
+    }
+
+    pub fn len(&self) -> usize {
+        self.inner.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.inner.is_empty()
+    }
+
+    pub fn memory_usage(&self) -> usize {
+        self.inner.memory_usage()
+    }
+
+    pub fn clear(&mut self) {
+        self.inner.clear()
+    }
+
+    pub fn drain(&mut self) -> Drain<'_, K, V> {
+        Drain { inner: self.inner.drain() }
+    }
+
+    // Where both contain an equal key, the one from `other` is kept.
+    pub fn append(&mut self, other: &mut Map<K, V, H>) {
+        self.inner.append(&mut other.inner)
+    }
+
+    pub fn split_off<Q>(&mut self, key: &Q) -> Map<K, V, H>
+    where
+        Q: Ord + ?Sized,
+        K: Borrow<Q>,
+    {
+        Map { inner: self.inner.split_off(QWrapper::new(key)) }
+    }
+
+    pub fn retain<F: FnMut(&K, &mut V) -> bool>(&mut self, mut f: F) {
+        self.inner.retain_mut(|KeyValue(k, v)| f(k, v))
+    }
+
+    pub fn extract_if<F: FnMut(&K, &mut V) -> bool>(&mut self, f: F) -> ExtractIf<'_, K, V, F, H> {
+        ExtractIf { raw: RawExtract::new(&mut self.inner), pred: f }
+    }
+
+    pub fn iter(&self) -> Iter<'_, K, V> {
+        IntoIterator::into_iter(self)
+    }
+
+    // Iterates over the entries whose keys are greater than or equal to `key`.
+    pub fn iter_from<Q>(&self, key: &Q) -> Iter<'_, K, V>
+    where
+        Q: Ord + ?Sized,
+        K: Borrow<Q>,
+    {
+        Iter { inner: self.inner.elems_from(QWrapper::new(key)) }
+    }
+
+    pub fn keys(&self) -> Keys<'_, K, V> {
+        Keys { inner: self.iter() }
+    }
+
+    pub fn values(&self) -> Values<'_, K, V> {
+        Values { inner: self.iter() }
+    }
+
+    pub fn into_keys(self) -> IntoKeys<K, V> {
+        IntoKeys { inner: self.inner.into_elems() }
+    }
+
+    pub fn into_values(self) -> IntoValues<K, V> {
+        IntoValues { inner: self.inner.into_elems() }
+    }
+
+    pub fn range<Q, R>(&self, range: R) -> Range<'_, K, V, Q, R>
+    where
+        Q: Ord + ?Sized,
+        K: Borrow<Q>,
+        R: RangeBounds<Q>,
+    {
+        Range { inner: self.inner.range(QRange(range)) }
+    }
 }
 
+// An entry in a map, found by Map::entry. Because other threads may insert
+// into the map concurrently, a vacant entry is only vacant as of when it was
+// found: inserting into it returns the value another thread inserted in the
+// meantime, if there is one. Entries only give shared access to the value.
+pub enum Entry<'a, K, V, const H: usize = MAX_HEIGHT> {
+    Occupied(OccupiedEntry<'a, K, V>),
+    Vacant(VacantEntry<'a, K, V, H>),
+}
+
+pub struct OccupiedEntry<'a, K, V> {
+    key: &'a K,
+    value: &'a V,
+}
+
+pub struct VacantEntry<'a, K, V, const H: usize = MAX_HEIGHT> {
+    key: K,
+    map: &'a Map<K, V, H>,
+}
+
+impl<'a, K: Ord, V, const H: usize> Entry<'a, K, V, H> {
+    pub fn key(&self) -> &K {
+        match self {
+            Entry::Occupied(entry)  => entry.key(),
+            Entry::Vacant(entry)    => entry.key(),
+        }
+    }
+
+    pub fn or_insert(self, default: V) -> &'a V {
+        self.or_insert_with(|| default)
+    }
+
+    pub fn or_insert_with<F: FnOnce() -> V>(self, default: F) -> &'a V {
+        match self {
+            Entry::Occupied(entry)  => entry.get(),
+            Entry::Vacant(entry)    => entry.insert(default()),
+        }
+    }
+
+    pub fn or_default(self) -> &'a V where V: Default {
+        self.or_insert_with(V::default)
+    }
+
+    // Values are shared with other threads, so they can only be modified
+    // through interior mutability.
+    pub fn and_modify<F: FnOnce(&V)>(self, f: F) -> Self {
+        if let Entry::Occupied(entry) = &self {
+            f(entry.get());
+        }
+        self
+    }
+}
+
+impl<'a, K, V> OccupiedEntry<'a, K, V> {
+    pub fn key(&self) -> &'a K {
+        self.key
+    }
+
+    pub fn get(&self) -> &'a V {
+        self.value
+    }
+}
+
+impl<'a, K: Ord, V, const H: usize> VacantEntry<'a, K, V, H> {
+    pub fn key(&self) -> &K {
+        &self.key
+    }
+
+    pub fn into_key(self) -> K {
+        self.key
+    }
+
+    // If another thread has inserted this key since the entry was found, the
+    // value is dropped and the value that thread inserted is returned.
+    pub fn insert(self, value: V) -> &'a V {
+        &self.map.inner.get_or_insert(KeyValue(self.key, value)).1
+    }
+}
+
+#[derive(Clone)]
 struct KeyValue<K, V>(K, V);
 
 impl<K: Ord, V> AbstractOrd<KeyValue<K, V>> for KeyValue<K, V> {
@@ -60,24 +376,717 @@ where
     }
 }
 
-impl<K: Ord, V> Extend<(K, V)> for Map<K, V> {
+// A MapBy is a map whose keys are ordered by a comparator function chosen at
+// runtime, rather than by their Ord implementation, for keys which should not
+// or cannot implement Ord, such as composite keys ordered only by a prefix.
+// Like SkipListBy, the comparator must be a total order.
+pub struct MapBy<K, V, F, const H: usize = MAX_HEIGHT> {
+    inner: SkipList<KeyValue<K, V>, H>,
+    cmp: F,
+}
+
+impl<K, V, F: Fn(&K, &K) -> Ordering> MapBy<K, V, F> {
+    pub fn with_comparator(cmp: F) -> MapBy<K, V, F> {
+        MapBy { inner: SkipList::unordered(), cmp }
+    }
+}
+
+impl<K, V, F: Fn(&K, &K) -> Ordering, const H: usize> MapBy<K, V, F, H> {
+    pub fn insert(&self, key: K, value: V) -> Option<(K, V, &K, &V)> {
+        let cmp = |x: &KeyValue<K, V>, y: &KeyValue<K, V>| (self.cmp)(&x.0, &y.0);
+        self.inner.insert_by(KeyValue(key, value), cmp).err().map(|(KeyValue(k, v), kv)| (k, v, &kv.0, &kv.1))
+    }
+
+    pub fn contains(&self, key: &K) -> bool {
+        self.get(key).is_some()
+    }
+
+    pub fn get(&self, key: &K) -> Option<&V> {
+        self.get_key_value(key).map(|(_, v)| v)
+    }
+
+    pub fn get_key_value(&self, key: &K) -> Option<(&K, &V)> {
+        self.inner.get(&ByKey { key, cmp: &self.cmp }).map(|KeyValue(k, v)| (k, v))
+    }
+
+    pub fn len(&self) -> usize {
+        self.inner.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.inner.is_empty()
+    }
+
+    pub fn iter(&self) -> Iter<'_, K, V> {
+        Iter { inner: self.inner.elems() }
+    }
+}
+
+// Compares a key against the keys of a MapBy with its comparator.
+struct ByKey<'a, K, F> {
+    key: &'a K,
+    cmp: &'a F,
+}
+
+impl<'a, K, V, F: Fn(&K, &K) -> Ordering> AbstractOrd<KeyValue<K, V>> for ByKey<'a, K, F> {
+    fn cmp(&self, rhs: &KeyValue<K, V>) -> Ordering {
+        (self.cmp)(self.key, &rhs.0)
+    }
+}
+
+impl<K: fmt::Debug, V: fmt::Debug, const H: usize> fmt::Debug for Map<K, V, H> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_map().entries(self).finish()
+    }
+}
+
+// Both maps iterate in the order of their keys, so they are equal exactly
+// when they yield equal entries in the same order.
+impl<K: Ord, V: PartialEq, const H: usize> PartialEq for Map<K, V, H> {
+    fn eq(&self, other: &Map<K, V, H>) -> bool {
+        self.len() == other.len() && self.iter().eq(other)
+    }
+}
+
+impl<K: Ord, V: Eq, const H: usize> Eq for Map<K, V, H> { }
+
+// Entries are hashed in the order of their keys, so maps which are equal hash
+// equally however they were built.
+impl<K: Ord + Hash, V: Hash, const H: usize> Hash for Map<K, V, H> {
+    fn hash<S: Hasher>(&self, state: &mut S) {
+        state.write_usize(self.len());
+        for entry in self {
+            entry.hash(state);
+        }
+    }
+}
+
+impl<K, V, Q, const H: usize> Index<&Q> for Map<K, V, H>
+where
+    Q: Ord + ?Sized,
+    K: Ord + Borrow<Q>,
+{
+    type Output = V;
+
+    fn index(&self, key: &Q) -> &V {
+        self.get(key).expect("no entry found for key")
+    }
+}
+
+impl<K: Ord, V, const H: usize> Default for Map<K, V, H> {
+    fn default() -> Self {
+        Map::with_height()
+    }
+}
+
+impl<K, V, const H: usize> IntoIterator for Map<K, V, H> {
+    type IntoIter = IntoIter<K, V>;
+    type Item = (K, V);
+    fn into_iter(self) -> IntoIter<K, V> {
+        IntoIter { inner: self.inner.into_elems() }
+    }
+}
+
+impl<'a, K, V, const H: usize> IntoIterator for &'a Map<K, V, H> {
+    type IntoIter = Iter<'a, K, V>;
+    type Item = (&'a K, &'a V);
+    fn into_iter(self) -> Iter<'a, K, V> {
+        Iter { inner: self.inner.elems() }
+    }
+}
+
+pub struct IntoIter<K, V> {
+    inner: IntoElems<KeyValue<K, V>>,
+}
+
+impl<K, V> Iterator for IntoIter<K, V> {
+    type Item = (K, V);
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next().map(|KeyValue(k, v)| (k, v))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+// The consuming iterators own the list, so they can walk it backward as well;
+// see IntoElems.
+impl<K, V> DoubleEndedIterator for IntoIter<K, V> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.inner.next_back().map(|KeyValue(k, v)| (k, v))
+    }
+}
+
+impl<K, V> ExactSizeIterator for IntoIter<K, V> { }
+
+impl<K, V> FusedIterator for IntoIter<K, V> { }
+
+pub struct Drain<'a, K, V> {
+    inner: skiplist::Drain<'a, KeyValue<K, V>>,
+}
+
+impl<'a, K, V> Iterator for Drain<'a, K, V> {
+    type Item = (K, V);
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next().map(|KeyValue(k, v)| (k, v))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<'a, K, V> DoubleEndedIterator for Drain<'a, K, V> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.inner.next_back().map(|KeyValue(k, v)| (k, v))
+    }
+}
+
+impl<'a, K, V> ExactSizeIterator for Drain<'a, K, V> { }
+
+impl<'a, K, V> FusedIterator for Drain<'a, K, V> { }
+
+// Extracts each entry for which the predicate returns true; see
+// SkipList::extract_if.
+pub struct ExtractIf<'a, K, V, F, const H: usize = MAX_HEIGHT> {
+    raw: RawExtract<'a, KeyValue<K, V>, H, skiplist::Global>,
+    pred: F,
+}
+
+impl<'a, K, V, F, const H: usize> Iterator for ExtractIf<'a, K, V, F, H>
+where
+    F: FnMut(&K, &mut V) -> bool,
+{
+    type Item = (K, V);
+    fn next(&mut self) -> Option<Self::Item> {
+        let pred = &mut self.pred;
+        self.raw.next(|KeyValue(k, v)| pred(k, v)).map(|KeyValue(k, v)| (k, v))
+    }
+}
+
+impl<'a, K, V, F, const H: usize> FusedIterator for ExtractIf<'a, K, V, F, H>
+where
+    F: FnMut(&K, &mut V) -> bool,
+{ }
+
+pub struct IntoKeys<K, V> {
+    inner: IntoElems<KeyValue<K, V>>,
+}
+
+impl<K, V> Iterator for IntoKeys<K, V> {
+    type Item = K;
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next().map(|KeyValue(k, _)| k)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<K, V> DoubleEndedIterator for IntoKeys<K, V> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.inner.next_back().map(|KeyValue(k, _)| k)
+    }
+}
+
+impl<K, V> ExactSizeIterator for IntoKeys<K, V> { }
+
+impl<K, V> FusedIterator for IntoKeys<K, V> { }
+
+pub struct IntoValues<K, V> {
+    inner: IntoElems<KeyValue<K, V>>,
+}
+
+impl<K, V> Iterator for IntoValues<K, V> {
+    type Item = V;
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next().map(|KeyValue(_, v)| v)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<K, V> DoubleEndedIterator for IntoValues<K, V> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.inner.next_back().map(|KeyValue(_, v)| v)
+    }
+}
+
+impl<K, V> ExactSizeIterator for IntoValues<K, V> { }
+
+impl<K, V> FusedIterator for IntoValues<K, V> { }
+
+// Iter is not an ExactSizeIterator: other threads may insert into the map
+// while it is being iterated.
+pub struct Iter<'a, K, V> {
+    inner: Elems<'a, KeyValue<K, V>>,
+}
+
+impl<'a, K, V> Clone for Iter<'a, K, V> {
+    fn clone(&self) -> Iter<'a, K, V> {
+        Iter { inner: self.inner.clone() }
+    }
+}
+
+impl<'a, K: 'a, V: 'a> Iterator for Iter<'a, K, V> {
+    type Item = (&'a K, &'a V);
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next().map(|KeyValue(k, v)| (k, v))
+    }
+
+    fn last(self) -> Option<Self::Item> {
+        self.inner.last().map(|KeyValue(k, v)| (k, v))
+    }
+}
+
+impl<'a, K: 'a, V: 'a> FusedIterator for Iter<'a, K, V> { }
+
+pub struct Keys<'a, K, V> {
+    inner: Iter<'a, K, V>,
+}
+
+impl<'a, K, V> Clone for Keys<'a, K, V> {
+    fn clone(&self) -> Keys<'a, K, V> {
+        Keys { inner: self.inner.clone() }
+    }
+}
+
+impl<'a, K: 'a, V: 'a> Iterator for Keys<'a, K, V> {
+    type Item = &'a K;
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next().map(|(k, _)| k)
+    }
+
+    fn last(self) -> Option<Self::Item> {
+        self.inner.last().map(|(k, _)| k)
+    }
+
+    fn max(self) -> Option<Self::Item> where Self::Item: Ord {
+        self.last()
+    }
+
+    fn min(mut self) -> Option<Self::Item> where Self::Item: Ord {
+        self.next()
+    }
+}
+
+impl<'a, K: 'a, V: 'a> FusedIterator for Keys<'a, K, V> { }
+
+pub struct Values<'a, K, V> {
+    inner: Iter<'a, K, V>,
+}
+
+impl<'a, K, V> Clone for Values<'a, K, V> {
+    fn clone(&self) -> Values<'a, K, V> {
+        Values { inner: self.inner.clone() }
+    }
+}
+
+impl<'a, K: 'a, V: 'a> Iterator for Values<'a, K, V> {
+    type Item = &'a V;
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next().map(|(_, v)| v)
+    }
+
+    fn last(self) -> Option<Self::Item> {
+        self.inner.last().map(|(_, v)| v)
+    }
+}
+
+impl<'a, K: 'a, V: 'a> FusedIterator for Values<'a, K, V> { }
+
+pub struct Range<'a, K, V, Q: ?Sized, R> {
+    inner: skiplist::Range<'a, KeyValue<K, V>, QWrapper<Q>, QRange<R>>,
+}
+
+impl<'a, K, V, Q, R> Iterator for Range<'a, K, V, Q, R>
+where
+    K: Ord + Borrow<Q> + 'a,
+    V: 'a,
+    Q: Ord + ?Sized,
+    R: RangeBounds<Q>,
+{
+    type Item = (&'a K, &'a V);
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next().map(|KeyValue(k, v)| (k, v))
+    }
+}
+
+impl<'a, K, V, Q, R> FusedIterator for Range<'a, K, V, Q, R>
+where
+    K: Ord + Borrow<Q> + 'a,
+    V: 'a,
+    Q: Ord + ?Sized,
+    R: RangeBounds<Q>,
+{ }
+
+impl<K: Ord, V, const H: usize> Extend<(K, V)> for Map<K, V, H> {
     fn extend<I: IntoIterator<Item = (K, V)>>(&mut self, iter: I) {
         let iter = iter.into_iter().map(|(key, value)| KeyValue(key, value));
         self.inner.extend(iter);
     }
 }
 
-impl<'a, K: Ord + Copy, V: Copy> Extend<(&'a K, &'a V)> for Map<K, V> {
+impl<'a, K: Ord + Copy, V: Copy, const H: usize> Extend<(&'a K, &'a V)> for Map<K, V, H> {
     fn extend<I: IntoIterator<Item = (&'a K, &'a V)>>(&mut self, iter: I) {
         let iter = iter.into_iter().map(|(&key, &value)| KeyValue(key, value));
         self.inner.extend(iter);
     }
 }
 
-impl<K: Ord, V> FromIterator<(K, V)> for Map<K, V> {
+impl<K: Ord, V, const N: usize> From<[(K, V); N]> for Map<K, V> {
+    fn from(entries: [(K, V); N]) -> Self {
+        Map::from_iter(entries)
+    }
+}
+
+// A BTreeMap is already sorted, so the list is built without searching.
+impl<K: Ord, V> From<BTreeMap<K, V>> for Map<K, V> {
+    fn from(map: BTreeMap<K, V>) -> Self {
+        Map { inner: SkipList::from_sorted_iter(map.into_iter().map(|(k, v)| KeyValue(k, v))) }
+    }
+}
+
+impl<K: Ord, V, const H: usize> From<Map<K, V, H>> for BTreeMap<K, V> {
+    fn from(map: Map<K, V, H>) -> Self {
+        map.into_iter().collect()
+    }
+}
+
+impl<K: Ord, V, const H: usize> FromIterator<(K, V)> for Map<K, V, H> {
     fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
-        let mut map = Self::new();
+        let mut map = Self::with_height();
         map.extend(iter);
         map
     }
 }
+
+#[cfg(feature = "std")]
+#[test]
+fn test_pop() {
+    let mut map: Map<i32, String> = (0..4).map(|x| (x, x.to_string())).collect();
+    assert_eq!(map.remove_first(), Some((&0, &String::from("0"))));
+    assert_eq!(map.pop_first(), Some((1, String::from("1"))));
+    assert_eq!(map.pop_last(), Some((3, String::from("3"))));
+    assert_eq!(map.len(), 1);
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn test_update() {
+    use std::sync::atomic::{AtomicU64, Ordering::Relaxed};
+
+    let counts: Map<u64, AtomicU64> = Map::new();
+    std::thread::scope(|scope| {
+        for _ in 0..4 {
+            scope.spawn(|| for i in 0..1_000 {
+                let key = i % 10;
+                if !counts.update(&key, |count| { count.fetch_add(1, Relaxed); }) {
+                    counts.get_or_insert_with(key, || AtomicU64::new(0)).fetch_add(1, Relaxed);
+                }
+            });
+        }
+    });
+    assert!(counts.values().all(|count| count.load(Relaxed) == 400));
+    assert!(!counts.update(&10, |_| unreachable!()));
+}
+
+#[test]
+fn test_map_by() {
+    // Keys are ordered, and compared, by their first field alone.
+    #[derive(Debug, PartialEq)]
+    struct Key(u32, &'static str);
+
+    let map = MapBy::with_comparator(|x: &Key, y: &Key| Ord::cmp(&x.0, &y.0));
+    assert!(map.insert(Key(2, "b"), 20).is_none());
+    assert!(map.insert(Key(1, "a"), 10).is_none());
+    let (key, value, existing, _) = map.insert(Key(2, "c"), 30).unwrap();
+    assert_eq!((key, value, existing), (Key(2, "c"), 30, &Key(2, "b")));
+    assert_eq!(map.get(&Key(2, "")), Some(&20));
+    assert_eq!(map.get_key_value(&Key(1, "")), Some((&Key(1, "a"), &10)));
+    assert!(!map.contains(&Key(3, "b")));
+    assert!(map.iter().map(|(_, &v)| v).eq([10, 20]));
+    assert_eq!(map.len(), 2);
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn test_with_mut() {
+    use std::sync::atomic::{AtomicU64, Ordering::Relaxed};
+
+    let counts: Map<&str, AtomicU64> = Map::new();
+    counts.insert("a", AtomicU64::new(0));
+    std::thread::scope(|scope| {
+        for _ in 0..4 {
+            scope.spawn(|| for _ in 0..100 {
+                counts.with_mut("a", |count| count.fetch_add(1, Relaxed));
+            });
+        }
+    });
+    assert_eq!(counts.with_mut("a", |count| count.fetch_add(1, Relaxed)), Some(400));
+    assert_eq!(counts.with_mut("b", |count| count.load(Relaxed)), None);
+}
+
+#[test]
+fn test_get_mut() {
+    let mut map: Map<_, _> = (0..10).map(|x| (x, x)).collect();
+    *map.get_mut(&4).unwrap() *= 4;
+    assert_eq!(map.get_mut(&10), None);
+    assert_eq!(map.get(&4), Some(&16));
+    assert_eq!(map[&4], 16);
+}
+
+#[test]
+fn test_get_disjoint_mut() {
+    let mut accounts: Map<&str, i32> = Map::from([("a", 100), ("b", 50)]);
+    let [from, to] = accounts.get_disjoint_mut(["a", "b"]).unwrap();
+    *from -= 30;
+    *to += 30;
+    assert_eq!(accounts.get("a"), Some(&70));
+    assert_eq!(accounts.get("b"), Some(&80));
+    assert!(accounts.get_disjoint_mut(["a", "a"]).is_none());
+    assert!(accounts.get_disjoint_mut(["a", "c"]).is_none());
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn test_get_key_value_mut() {
+    let mut map: Map<String, usize> = ["a", "bb", "ccc"].iter().map(|s| (s.to_string(), 0)).collect();
+    if let Some((key, value)) = map.get_key_value_mut("bb") {
+        *value = key.len();
+    }
+    assert_eq!(map.get("bb"), Some(&2));
+    assert_eq!(map.get_key_value_mut("d"), None);
+}
+
+#[cfg(feature = "std")]
+#[test]
+#[should_panic(expected = "no entry found for key")]
+fn test_index_missing() {
+    let map: Map<String, i32> = Map::new();
+    let _ = map["missing"];
+}
+
+#[test]
+fn test_iter() {
+    let map: Map<_, _> = (0..10).rev().map(|x| (x, x * 2)).collect();
+    assert!(map.iter().map(|(&k, &v)| (k, v)).eq((0..10).map(|x| (x, x * 2))));
+    let mut iter = map.into_iter();
+    assert_eq!(iter.len(), 10);
+    assert_eq!(iter.next(), Some((0, 0)));
+    assert_eq!(iter.len(), 9);
+}
+
+#[test]
+fn test_btree_map() {
+    let btree: BTreeMap<i32, i32> = (0..100).map(|x| (x, x * 2)).collect();
+    let map = Map::from(btree.clone());
+    assert!(map.iter().eq(btree.iter()));
+    assert_eq!(map.get(&50), Some(&100));
+    assert_eq!(BTreeMap::from(map), btree);
+}
+
+#[test]
+fn test_iter_last() {
+    let map: Map<_, _> = (0..1000).map(|x| (x, -x)).collect();
+    assert_eq!(map.iter().last(), Some((&999, &-999)));
+    assert_eq!(map.keys().max(), Some(&999));
+    assert_eq!(map.keys().min(), Some(&0));
+    assert_eq!(map.values().last(), Some(&-999));
+}
+
+#[test]
+fn test_iter_from() {
+    let map: Map<_, _> = (0..10).map(|x| (x * 2, x)).collect();
+    assert!(map.iter_from(&7).map(|(&k, _)| k).eq([8, 10, 12, 14, 16, 18]));
+    assert!(map.iter_from(&8).map(|(&k, _)| k).eq([8, 10, 12, 14, 16, 18]));
+    assert_eq!(map.iter_from(&-1).count(), 10);
+    assert_eq!(map.iter_from(&19).next(), None);
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn test_append() {
+    use std::rc::Rc;
+
+    let rc = Rc::new(());
+    let mut map: Map<_, _> = (0..10).map(|x| (x, (0, rc.clone()))).collect();
+    let mut other: Map<_, _> = (5..15).map(|x| (x, (1, rc.clone()))).collect();
+    map.append(&mut other);
+
+    assert!(other.is_empty());
+    assert_eq!(other.iter().count(), 0);
+    assert_eq!(map.len(), 15);
+    assert!(map.iter().map(|(&k, &(v, _))| (k, v)).eq((0..15).map(|x| (x, (x >= 5) as i32))));
+    assert_eq!(Rc::strong_count(&rc), 16);
+
+    other.insert(20, (1, rc.clone()));
+    assert_eq!(other.len(), 1);
+    drop((map, other));
+    assert_eq!(Rc::strong_count(&rc), 1);
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn test_entry() {
+    use std::cell::Cell;
+
+    let map: Map<i32, Cell<i32>> = Map::new();
+    assert_eq!(map.entry(0).key(), &0);
+    assert_eq!(map.entry(0).or_insert(Cell::new(1)).get(), 1);
+    assert_eq!(map.entry(0).or_insert(Cell::new(2)).get(), 1);
+    map.entry(0).and_modify(|v| v.set(v.get() + 10)).or_default();
+    map.entry(1).and_modify(|v| v.set(v.get() + 10)).or_default();
+    assert_eq!(map.get(&0).map(Cell::get), Some(11));
+    assert_eq!(map.get(&1).map(Cell::get), Some(0));
+
+    match map.entry(2) {
+        Entry::Occupied(_)      => panic!("entry should be vacant"),
+        Entry::Vacant(entry)    => {
+            map.insert(2, Cell::new(5));
+            assert_eq!(entry.insert(Cell::new(6)).get(), 5);
+        }
+    }
+    assert_eq!(map.len(), 3);
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn test_get_or_insert_with() {
+    use std::cell::Cell;
+
+    let calls = Cell::new(0);
+    let f = || {
+        calls.set(calls.get() + 1);
+        calls.get() * 10
+    };
+
+    let map: Map<i32, i32> = Map::new();
+    assert_eq!(map.get_or_insert_with(0, f), &10);
+    assert_eq!(calls.get(), 1);
+    assert_eq!(map.get_or_insert_with(0, f), &10);
+    assert_eq!(calls.get(), 1);
+    assert_eq!(map.get_or_insert_with(1, f), &20);
+    assert_eq!(calls.get(), 2);
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn test_compute_if_present() {
+    let mut map: Map<_, _> = (0..10).map(|x| (x.to_string(), x)).collect();
+    assert_eq!(map.compute_if_present("4", |_, v| Some(v * 10)), Some(&40));
+    assert_eq!(map.get("4"), Some(&40));
+    assert_eq!(map.compute_if_present("5", |_, _| None), None);
+    assert_eq!(map.get("5"), None);
+    assert_eq!(map.compute_if_present("10", |_, v| Some(v)), None);
+    assert_eq!(map.len(), 9);
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn test_range() {
+    use std::ops::Bound::*;
+
+    let map: Map<_, _> = (0..100).map(|x| (x.to_string(), x)).collect();
+    let range: Vec<_> = map.range::<str, _>((Included("20"), Excluded("25"))).map(|(_, &v)| v).collect();
+    assert_eq!(range, [20, 21, 22, 23, 24]);
+    let range: Vec<_> = map.range::<str, _>((Excluded("97"), Unbounded)).map(|(_, &v)| v).collect();
+    assert_eq!(range, [98, 99]);
+
+    let map: Map<_, _> = (0..100).map(|x| (x, x * 2)).collect();
+    assert_eq!(map.range_count(10..=12), 3);
+    assert!(map.range(10..=12).eq([(&10, &20), (&11, &22), (&12, &24)]));
+    assert!(map.keys().copied().eq(0..100));
+    assert!(map.values().copied().eq((0..100).map(|x| x * 2)));
+}
+
+#[test]
+fn test_ceiling_key_value() {
+    // Intervals keyed by their start, every tenth one removed.
+    let mut map: Map<u32, u32> = (0..1000).map(|x| (x * 10, x * 10 + 5)).collect();
+    map.retain(|k, _| k % 100 != 0);
+    assert_eq!(map.ceiling_key_value(&20), Some((&20, &25)));
+    assert_eq!(map.ceiling_key_value(&21), Some((&30, &35)));
+    assert_eq!(map.ceiling_key_value(&95), Some((&110, &115)));
+    assert_eq!(map.ceiling_key_value(&9991), None);
+    assert!(map.range(95..130).map(|(&k, _)| k).eq([110, 120]));
+    assert_eq!(map.get(&100), None);
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn test_debug() {
+    let map: Map<_, _> = vec![(2, "b"), (1, "a")].into_iter().collect();
+    assert_eq!(format!("{:?}", map), r#"{1: "a", 2: "b"}"#);
+    assert_eq!(format!("{:?}", Map::<i32, i32>::new()), "{}");
+}
+
+#[test]
+fn test_eq() {
+    let a: Map<_, _> = (0..10).map(|x| (x, x)).collect();
+    let b: Map<_, _> = (0..10).rev().map(|x| (x, x)).collect();
+    assert_eq!(a, b);
+    b.insert(10, 10);
+    assert_ne!(a, b);
+    a.insert(10, 11);
+    assert_ne!(a, b);
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn test_hash() {
+    use std::collections::hash_map::DefaultHasher;
+
+    fn hash<T: Hash>(value: &T) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        value.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    let a: Map<_, _> = (0..10).map(|x| (x, x)).collect();
+    let b: Map<_, _> = (0..10).rev().map(|x| (x, x)).collect();
+    assert_eq!(hash(&a), hash(&b));
+}
+
+#[test]
+fn test_into_iter_rev() {
+    let map: Map<i32, i32> = (0..10).map(|x| (x, x * 2)).collect();
+    assert!(map.clone().into_iter().rev().eq((0..10).rev().map(|x| (x, x * 2))));
+    assert!(map.clone().into_keys().rev().eq((0..10).rev()));
+    let mut iter = map.into_values();
+    assert_eq!((iter.next(), iter.next_back()), (Some(0), Some(18)));
+    assert_eq!(iter.len(), 8);
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn test_into_keys_values() {
+    let map: Map<_, _> = (0..10).map(|x| (x, x.to_string())).collect();
+    assert!(map.clone().into_keys().eq(0..10));
+    assert!(map.into_values().eq((0..10).map(|x| x.to_string())));
+}
+
+#[test]
+fn test_from_array() {
+    let map = Map::from([(2, "b"), (1, "a"), (2, "c")]);
+    assert_eq!(map.len(), 2);
+    assert_eq!(map[&1], "a");
+    assert_eq!(map[&2], "b");
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn test_height() {
+    use std::mem::size_of;
+
+    let map: Map<i32, i32, 2> = (0..100).map(|x| (x, x * 2)).collect();
+    assert_eq!(map.get(&40), Some(&80));
+    assert!(map.keys().copied().eq(0..100));
+    // The 29 lanes saved, less what the list is padded by to fill its last
+    // cache line.
+    assert!(size_of::<Map<i32, i32, 2>>() <= size_of::<Map<i32, i32>>() - 29 * size_of::<usize>() + 63);
+}
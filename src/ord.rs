@@ -1,7 +1,14 @@
-use std::borrow::Borrow;
-use std::cmp::Ordering;
+use core::borrow::Borrow;
+use core::cmp::Ordering;
+use core::ops::{Bound, RangeBounds};
 
-// Same requirements as Ord, but the LHS and RHS can be separate types
+// Same requirements as Ord, but the LHS and RHS can be separate types.
+//
+// Lists are searched by comparing the query against their elements, so a list
+// can be searched with any type which implements AbstractOrd for its element
+// type, not only with the types its elements can be borrowed as. The ordering
+// must be consistent with the order of the elements: if the query is less than
+// an element, it must be less than every greater element.
 pub trait AbstractOrd<Rhs> {
     fn cmp(&self, rhs: &Rhs) -> Ordering;
 }
@@ -12,12 +19,18 @@ impl<T: Ord> AbstractOrd<T> for T {
     }
 }
 
+// Adapts the Borrow model to AbstractOrd: a QWrapper<Q> compares against any
+// element which can be borrowed as Q, comparing the borrowed forms.
 #[repr(transparent)]
 pub struct QWrapper<Q: ?Sized>(pub Q);
 
 impl<Q: ?Sized> QWrapper<Q> {
+    // Because QWrapper is repr(transparent), it has the same layout as Q, and
+    // a reference to Q, including the metadata of an unsized Q, is a valid
+    // reference to QWrapper<Q> with the same lifetime. The wrapper adds no
+    // invariants of its own, so this cast is sound for every Q.
     pub fn new(value: &Q) -> &QWrapper<Q> {
-        unsafe { std::mem::transmute(value) }
+        unsafe { &*(value as *const Q as *const QWrapper<Q>) }
     }
 }
 
@@ -29,3 +42,51 @@ impl<T, Q> AbstractOrd<T> for QWrapper<Q> where
         Ord::cmp(&self.0, rhs.borrow())
     }
 }
+
+// A range of Q is a range of QWrapper<Q>.
+pub struct QRange<R>(pub R);
+
+impl<Q: ?Sized, R: RangeBounds<Q>> RangeBounds<QWrapper<Q>> for QRange<R> {
+    fn start_bound(&self) -> Bound<&QWrapper<Q>> {
+        self.0.start_bound().map(QWrapper::new)
+    }
+
+    fn end_bound(&self) -> Bound<&QWrapper<Q>> {
+        self.0.end_bound().map(QWrapper::new)
+    }
+}
+
+// Compares an element using a comparator function, rather than using the
+// element's own ordering.
+pub struct Comparing<'a, T, F> {
+    pub elem: &'a T,
+    pub cmp: &'a F,
+}
+
+impl<'a, T, F> AbstractOrd<T> for Comparing<'a, T, F> where
+    F: Fn(&T, &T) -> Ordering,
+{
+    fn cmp(&self, rhs: &T) -> Ordering {
+        (self.cmp)(self.elem, rhs)
+    }
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn test_abstract_ord() {
+    use crate::raw::SkipList;
+
+    // Search a list of pairs by the first element alone.
+    struct Prefix(u32);
+
+    impl AbstractOrd<(u32, &str)> for Prefix {
+        fn cmp(&self, rhs: &(u32, &str)) -> Ordering {
+            Ord::cmp(&self.0, &rhs.0)
+        }
+    }
+
+    let list: SkipList<(u32, &str)> = vec![(1, "a"), (2, "b"), (3, "c")].into_iter().collect();
+    assert_eq!(list.get(&Prefix(2)), Some(&(2, "b")));
+    assert_eq!(list.get(&Prefix(4)), None);
+    assert_eq!(list.get(QWrapper::new(&(3, "c"))), Some(&(3, "c")));
+}
@@ -0,0 +1,58 @@
+// Support for property testing, enabled by the `proptest` feature. Sets and
+// maps are generated by collecting an arbitrary vector, so duplicates in the
+// vector are discarded and the generated collections may be empty.
+use alloc::vec::Vec;
+use core::iter::FromIterator;
+
+use proptest::arbitrary::{any_with, Arbitrary};
+use proptest::collection::{vec, SizeRange, VecStrategy};
+use proptest::strategy::{Map as MapStrategy, Strategy};
+
+use crate::{Map, Set};
+
+impl<T: Arbitrary + Ord, const H: usize> Arbitrary for Set<T, H> {
+    type Parameters = (SizeRange, T::Parameters);
+    type Strategy = MapStrategy<VecStrategy<T::Strategy>, fn(Vec<T>) -> Set<T, H>>;
+
+    fn arbitrary_with((size, args): Self::Parameters) -> Self::Strategy {
+        vec(any_with::<T>(args), size).prop_map(Set::from_iter)
+    }
+}
+
+impl<K: Arbitrary + Ord, V: Arbitrary, const H: usize> Arbitrary for Map<K, V, H> {
+    type Parameters = (SizeRange, K::Parameters, V::Parameters);
+    type Strategy = MapStrategy<VecStrategy<(K::Strategy, V::Strategy)>, fn(Vec<(K, V)>) -> Map<K, V, H>>;
+
+    fn arbitrary_with((size, k, v): Self::Parameters) -> Self::Strategy {
+        vec((any_with::<K>(k), any_with::<V>(v)), size).prop_map(Map::from_iter)
+    }
+}
+
+#[cfg(test)]
+proptest::proptest! {
+    // Small elements make overlaps between the sets, and long runs of equal
+    // elements, likely.
+    #[test]
+    fn test_set_operations_match_btree(
+        a in proptest::collection::vec(0u8..32, 0..40),
+        b in proptest::collection::vec(0u8..32, 0..40),
+    ) {
+        use alloc::collections::BTreeSet;
+
+        let (set_a, set_b) = (Set::<u8>::from_iter(a.clone()), Set::<u8>::from_iter(b.clone()));
+        let (tree_a, tree_b) = (BTreeSet::from_iter(a), BTreeSet::from_iter(b));
+
+        proptest::prop_assert!(set_a.union(&set_b).eq(tree_a.union(&tree_b)));
+        proptest::prop_assert!(set_a.intersection(&set_b).eq(tree_a.intersection(&tree_b)));
+        proptest::prop_assert!(set_a.difference(&set_b).eq(tree_a.difference(&tree_b)));
+        proptest::prop_assert!(set_a.symmetric_difference(&set_b).eq(tree_a.symmetric_difference(&tree_b)));
+        proptest::prop_assert_eq!(set_a.is_subset(&set_b), tree_a.is_subset(&tree_b));
+        proptest::prop_assert_eq!(set_a.is_disjoint(&set_b), tree_a.is_disjoint(&tree_b));
+        proptest::prop_assert!(set_a.clone().into_union(set_b.clone()).iter().eq(tree_a.union(&tree_b)));
+    }
+
+    #[test]
+    fn test_arbitrary_map(map in proptest::arbitrary::any::<Map<u8, u8>>()) {
+        proptest::prop_assert!(map.keys().zip(map.keys().skip(1)).all(|(x, y)| x < y));
+    }
+}
@@ -0,0 +1,85 @@
+// Parallel iteration, enabled by the `rayon` feature. A list cannot be split
+// without walking it, so the elements are first collected into a vector of
+// references, which rayon then splits among its threads. Like the sequential
+// iterators, this may or may not see elements inserted concurrently.
+//
+// Because insert only needs a shared reference, sets and maps are built and
+// extended in parallel by every worker inserting into the same list. When the
+// source contains duplicates, which of them is kept depends on which worker
+// inserts first.
+use alloc::vec::Vec;
+
+use rayon::iter::{FromParallelIterator, IntoParallelIterator, ParallelExtend, ParallelIterator};
+use rayon::vec::IntoIter;
+
+use crate::{Map, Set};
+
+impl<'a, T: Ord + Sync, const H: usize> IntoParallelIterator for &'a Set<T, H> {
+    type Iter = IntoIter<&'a T>;
+    type Item = &'a T;
+
+    fn into_par_iter(self) -> IntoIter<&'a T> {
+        self.iter().collect::<Vec<_>>().into_par_iter()
+    }
+}
+
+impl<'a, K: Ord + Sync, V: Sync, const H: usize> IntoParallelIterator for &'a Map<K, V, H> {
+    type Iter = IntoIter<(&'a K, &'a V)>;
+    type Item = (&'a K, &'a V);
+
+    fn into_par_iter(self) -> IntoIter<(&'a K, &'a V)> {
+        self.iter().collect::<Vec<_>>().into_par_iter()
+    }
+}
+
+impl<T: Ord + Send + Sync, const H: usize> ParallelExtend<T> for Set<T, H> {
+    fn par_extend<I: IntoParallelIterator<Item = T>>(&mut self, iter: I) {
+        let set = &*self;
+        iter.into_par_iter().for_each(|elem| { set.insert(elem); });
+    }
+}
+
+impl<T: Ord + Send + Sync, const H: usize> FromParallelIterator<T> for Set<T, H> {
+    fn from_par_iter<I: IntoParallelIterator<Item = T>>(iter: I) -> Self {
+        let mut set = Self::with_height();
+        set.par_extend(iter);
+        set
+    }
+}
+
+impl<K: Ord + Send + Sync, V: Send + Sync, const H: usize> ParallelExtend<(K, V)> for Map<K, V, H> {
+    fn par_extend<I: IntoParallelIterator<Item = (K, V)>>(&mut self, iter: I) {
+        let map = &*self;
+        iter.into_par_iter().for_each(|(key, value)| { map.insert(key, value); });
+    }
+}
+
+impl<K: Ord + Send + Sync, V: Send + Sync, const H: usize> FromParallelIterator<(K, V)> for Map<K, V, H> {
+    fn from_par_iter<I: IntoParallelIterator<Item = (K, V)>>(iter: I) -> Self {
+        let mut map = Self::with_height();
+        map.par_extend(iter);
+        map
+    }
+}
+
+#[test]
+fn test_par_iter() {
+    use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
+
+    let set: Set<u64> = (0..10_000).collect();
+    assert_eq!(set.par_iter().sum::<u64>(), 49_995_000);
+
+    let map: Map<u64, u64> = (0..10_000).map(|x| (x, x * 2)).collect();
+    assert_eq!(map.par_iter().map(|(_, v)| v).sum::<u64>(), 99_990_000);
+}
+
+#[test]
+fn test_from_par_iter() {
+    let set: Set<u64> = (0..10_000u64).into_par_iter().map(|x| x % 5_000).collect();
+    assert_eq!(set.len(), 5_000);
+    assert!(set.iter().copied().eq(0..5_000));
+
+    let mut map: Map<u64, u64> = (0..1_000u64).into_par_iter().map(|x| (x, x * 2)).collect();
+    map.par_extend((1_000..2_000u64).into_par_iter().map(|x| (x, x * 2)));
+    assert!(map.iter().map(|(&k, &v)| (k, v)).eq((0..2_000).map(|x| (x, x * 2))));
+}
@@ -0,0 +1,112 @@
+// Serialization support, enabled by the `serde` feature. Lists and sets are
+// serialized as sequences and maps as maps, in order. Deserializing inserts
+// each element, so the input does not need to be sorted; later duplicates are
+// discarded.
+use core::fmt;
+use core::marker::PhantomData;
+
+use serde::de::{Deserialize, Deserializer, MapAccess, SeqAccess, Visitor};
+use serde::ser::{Serialize, Serializer};
+
+use crate::{AbstractOrd, Map, Set, SkipList};
+
+impl<T: Serialize, const H: usize> Serialize for SkipList<T, H> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(self.elems())
+    }
+}
+
+impl<T: Ord + Serialize, const H: usize> Serialize for Set<T, H> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(self.iter())
+    }
+}
+
+impl<K: Ord + Serialize, V: Serialize, const H: usize> Serialize for Map<K, V, H> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_map(self.iter())
+    }
+}
+
+struct SeqVisitor<C, T>(PhantomData<fn() -> (C, T)>);
+
+impl<'de, C, T> Visitor<'de> for SeqVisitor<C, T>
+where
+    C: Default + Extend<T>,
+    T: Deserialize<'de>,
+{
+    type Value = C;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a sequence")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<C, A::Error> {
+        let mut collection = C::default();
+        while let Some(elem) = seq.next_element()? {
+            collection.extend(Some(elem));
+        }
+        Ok(collection)
+    }
+}
+
+impl<'de, T, const H: usize> Deserialize<'de> for SkipList<T, H>
+where
+    T: AbstractOrd<T> + Deserialize<'de>,
+{
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_seq(SeqVisitor(PhantomData))
+    }
+}
+
+impl<'de, T: Ord + Deserialize<'de>, const H: usize> Deserialize<'de> for Set<T, H> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_seq(SeqVisitor(PhantomData))
+    }
+}
+
+struct MapVisitor<K, V, const H: usize>(PhantomData<fn() -> (K, V)>);
+
+impl<'de, K, V, const H: usize> Visitor<'de> for MapVisitor<K, V, H>
+where
+    K: Ord + Deserialize<'de>,
+    V: Deserialize<'de>,
+{
+    type Value = Map<K, V, H>;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a map")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut access: A) -> Result<Map<K, V, H>, A::Error> {
+        let map = Map::with_height();
+        while let Some((key, value)) = access.next_entry()? {
+            map.insert(key, value);
+        }
+        Ok(map)
+    }
+}
+
+impl<'de, K, V, const H: usize> Deserialize<'de> for Map<K, V, H>
+where
+    K: Ord + Deserialize<'de>,
+    V: Deserialize<'de>,
+{
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_map(MapVisitor(PhantomData))
+    }
+}
+
+#[test]
+fn test_round_trip() {
+    let list: SkipList<i32> = serde_json::from_str("[3, 1, 2, 1]").unwrap();
+    assert_eq!(serde_json::to_string(&list).unwrap(), "[1,2,3]");
+
+    let set: Set<String> = serde_json::from_str(r#"["b", "a"]"#).unwrap();
+    assert_eq!(serde_json::to_string(&set).unwrap(), r#"["a","b"]"#);
+
+    let map: Map<String, i32> = serde_json::from_str(r#"{"b": 2, "a": 1}"#).unwrap();
+    let json = serde_json::to_string(&map).unwrap();
+    assert_eq!(json, r#"{"a":1,"b":2}"#);
+    assert!(serde_json::from_str::<Map<String, i32>>(&json).unwrap() == map);
+}
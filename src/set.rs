@@ -1,22 +1,71 @@
-use std::borrow::Borrow;
-use std::iter::FromIterator;
+use alloc::collections::BTreeSet;
+use core::borrow::Borrow;
+use core::cmp::Ordering;
+use core::fmt;
+use core::hash::{Hash, Hasher};
+use core::iter::{self, FromIterator, FusedIterator, Peekable};
+use core::ops::{BitAnd, BitOr, BitXor, RangeBounds, Sub};
 
 use crate::{SkipList, QWrapper};
+use crate::ord::QRange;
 use crate::skiplist::*;
 
-pub struct Set<T> {
-    inner: SkipList<T>,
+#[derive(Clone)]
+pub struct Set<T, const H: usize = MAX_HEIGHT> {
+    inner: SkipList<T, H>,
 }
 
 impl<T: Ord> Set<T> {
     pub fn new() -> Set<T> {
         Set { inner: SkipList::new() }
     }
+}
+
+impl<T: Ord, const H: usize> Set<T, H> {
+    // Construct a set whose list has at most `H` lanes; see SkipList.
+    pub fn with_height() -> Set<T, H> {
+        Set { inner: SkipList::with_height() }
+    }
+
+    // Construct a set expecting to hold about `capacity` elements; see SkipList.
+    pub fn with_capacity(capacity: usize) -> Set<T, H> {
+        Set { inner: SkipList::with_capacity(capacity) }
+    }
+
+    // Construct a set which draws node heights from `source`; see SkipList.
+    pub fn with_height_source(source: fn() -> u64) -> Set<T, H> {
+        Set { inner: SkipList::with_height_source(source) }
+    }
 
     pub fn insert(&self, elem: T) -> Option<(T, &T)> {
         self.inner.insert(elem)
     }
 
+    pub fn insert_and_ref(&self, elem: T) -> Result<&T, (T, &T)> {
+        self.inner.insert_and_ref(elem)
+    }
+
+    // Inserts a clone of `elem` only if no equal element is present; see
+    // SkipList::try_insert_ref.
+    pub fn try_insert_ref(&self, elem: &T) -> Option<&T>
+    where
+        T: Clone,
+    {
+        self.inner.try_insert_ref(elem)
+    }
+
+    pub fn replace(&mut self, value: T) -> Option<T> {
+        self.inner.replace(value)
+    }
+
+    pub fn get_or_insert(&self, elem: T) -> &T {
+        self.inner.get_or_insert(elem)
+    }
+
+    pub fn insert_ref(&self, elem: T) -> (&T, bool) {
+        self.inner.insert_ref(elem)
+    }
+
     pub fn contains<Q>(&self, value: &Q) -> bool
     where
         Q: Ord + ?Sized,
@@ -33,12 +82,230 @@ impl<T: Ord> Set<T> {
         self.inner.get(QWrapper::new(value))
     }
 
+    pub fn first(&self) -> Option<&T> {
+        self.inner.first()
+    }
+
+    pub fn last(&self) -> Option<&T> {
+        self.inner.last()
+    }
+
+    pub fn floor<Q>(&self, value: &Q) -> Option<&T>
+    where
+        Q: Ord + ?Sized,
+        T: Borrow<Q>,
+    {
+        self.inner.get_le(QWrapper::new(value))
+    }
+
+    pub fn ceiling<Q>(&self, value: &Q) -> Option<&T>
+    where
+        Q: Ord + ?Sized,
+        T: Borrow<Q>,
+    {
+        self.inner.get_ge(QWrapper::new(value))
+    }
+
+    // Values removed from the set are not deallocated while the set is shared,
+    // so they are returned by reference rather than by value.
+    pub fn remove_first(&self) -> Option<&T> {
+        self.inner.remove_first()
+    }
+
+    pub fn remove_last(&self) -> Option<&T> {
+        self.inner.remove_last()
+    }
+
+    pub fn pop_first(&mut self) -> Option<T> {
+        self.inner.pop_first()
+    }
+
+    pub fn pop_last(&mut self) -> Option<T> {
+        self.inner.pop_last()
+    }
+
+    pub fn range_count<Q, R>(&self, range: R) -> usize
+    where
+        Q: Ord + ?Sized,
+        T: Borrow<Q>,
+        R: RangeBounds<Q>,
+    {
+        self.inner.range_count(QRange(range))
+    }
+
+    pub fn len(&self) -> usize {
+        self.inner.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.inner.is_empty()
+    }
+
+    pub fn memory_usage(&self) -> usize {
+        self.inner.memory_usage()
+    }
+
+    pub fn clear(&mut self) {
+        self.inner.clear()
+    }
+
+    pub fn drain(&mut self) -> Drain<'_, T> {
+        Drain { inner: self.inner.drain() }
+    }
+
+    // Where both contain an equal value, the one from `other` is kept.
+    pub fn append(&mut self, other: &mut Set<T, H>) {
+        self.inner.append(&mut other.inner)
+    }
+
+    pub fn split_off<Q>(&mut self, value: &Q) -> Set<T, H>
+    where
+        Q: Ord + ?Sized,
+        T: Borrow<Q>,
+    {
+        Set { inner: self.inner.split_off(QWrapper::new(value)) }
+    }
+
+    pub fn retain<F: FnMut(&T) -> bool>(&mut self, f: F) {
+        self.inner.retain(f)
+    }
+
+    pub fn extract_if<F: FnMut(&T) -> bool>(&mut self, f: F) -> ExtractIf<'_, T, F, H> {
+        ExtractIf { inner: self.inner.extract_if(f) }
+    }
+
     pub fn iter(&self) -> Iter<'_, T> {
         IntoIterator::into_iter(self)
     }
+
+    // Iterates over the elements greater than or equal to `value`.
+    pub fn iter_from<Q>(&self, value: &Q) -> Iter<'_, T>
+    where
+        Q: Ord + ?Sized,
+        T: Borrow<Q>,
+    {
+        Iter { inner: self.inner.elems_from(QWrapper::new(value)) }
+    }
+
+    pub fn difference<'a>(&'a self, other: &'a Set<T, H>) -> Difference<'a, T> {
+        Difference { merge: Merge::new(self, other) }
+    }
+
+    pub fn symmetric_difference<'a>(&'a self, other: &'a Set<T, H>) -> SymmetricDifference<'a, T> {
+        SymmetricDifference { merge: Merge::new(self, other) }
+    }
+
+    pub fn intersection<'a>(&'a self, other: &'a Set<T, H>) -> Intersection<'a, T> {
+        Intersection { merge: Merge::new(self, other) }
+    }
+
+    pub fn union<'a>(&'a self, other: &'a Set<T, H>) -> Union<'a, T> {
+        Union { merge: Merge::new(self, other) }
+    }
+
+    // These consume both sets, moving their values into the new set instead
+    // of cloning them. Of two equal values, the one from `self` is kept.
+    pub fn into_union(self, other: Set<T, H>) -> Set<T, H> {
+        let mut merge = Merge { a: self.into_iter().peekable(), b: other.into_iter().peekable() };
+        let union = iter::from_fn(|| match merge.next() {
+            (None, None)    => None,
+            (x, y)          => x.or(y),
+        });
+        Set { inner: SkipList::from_sorted_iter(union) }
+    }
+
+    pub fn into_intersection(self, other: Set<T, H>) -> Set<T, H> {
+        let mut merge = Merge { a: self.into_iter().peekable(), b: other.into_iter().peekable() };
+        let intersection = iter::from_fn(|| loop {
+            match merge.next() {
+                (Some(x), Some(_))  => return Some(x),
+                (None, None)        => return None,
+                _                   => { }
+            }
+        });
+        Set { inner: SkipList::from_sorted_iter(intersection) }
+    }
+
+    pub fn into_difference(self, other: Set<T, H>) -> Set<T, H> {
+        let mut merge = Merge { a: self.into_iter().peekable(), b: other.into_iter().peekable() };
+        let difference = iter::from_fn(|| loop {
+            match merge.next() {
+                (Some(x), None) => return Some(x),
+                (None, None)    => return None,
+                _               => { }
+            }
+        });
+        Set { inner: SkipList::from_sorted_iter(difference) }
+    }
+
+    // These walk both sets in order at the same time, so they take time
+    // linear in the size of the sets.
+    pub fn is_subset(&self, other: &Set<T, H>) -> bool {
+        let mut other = other.iter().peekable();
+        for value in self {
+            loop {
+                match other.peek().map(|x| value.cmp(x)) {
+                    Some(Ordering::Greater) => { other.next(); }
+                    Some(Ordering::Equal)   => break,
+                    _                       => return false,
+                }
+            }
+        }
+        true
+    }
+
+    pub fn is_superset(&self, other: &Set<T, H>) -> bool {
+        other.is_subset(self)
+    }
+
+    pub fn is_disjoint(&self, other: &Set<T, H>) -> bool {
+        let mut this = self.iter().peekable();
+        let mut other = other.iter().peekable();
+        while let (Some(x), Some(y)) = (this.peek(), other.peek()) {
+            match x.cmp(y) {
+                Ordering::Less      => { this.next(); }
+                Ordering::Greater   => { other.next(); }
+                Ordering::Equal     => return false,
+            }
+        }
+        true
+    }
+}
+
+impl<T: fmt::Debug, const H: usize> fmt::Debug for Set<T, H> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_set().entries(self).finish()
+    }
+}
+
+// Both sets iterate in order, so they are equal exactly when they yield equal
+// values in the same order.
+impl<T: Ord, const H: usize> PartialEq for Set<T, H> {
+    fn eq(&self, other: &Set<T, H>) -> bool {
+        self.len() == other.len() && self.iter().eq(other)
+    }
+}
+
+impl<T: Ord, const H: usize> Eq for Set<T, H> { }
+
+// Values are hashed in order, so sets which are equal hash equally however
+// they were built.
+impl<T: Ord + Hash, const H: usize> Hash for Set<T, H> {
+    fn hash<S: Hasher>(&self, state: &mut S) {
+        state.write_usize(self.len());
+        for value in self {
+            value.hash(state);
+        }
+    }
+}
+
+impl<T: Ord, const H: usize> Default for Set<T, H> {
+    fn default() -> Self {
+        Set::with_height()
+    }
 }
 
-impl<T> IntoIterator for Set<T> {
+impl<T, const H: usize> IntoIterator for Set<T, H> {
     type IntoIter = IntoIter<T>;
     type Item = T;
     fn into_iter(self) -> IntoIter<T> {
@@ -46,7 +313,7 @@ impl<T> IntoIterator for Set<T> {
     }
 }
 
-impl<'a, T> IntoIterator for &'a Set<T> {
+impl<'a, T, const H: usize> IntoIterator for &'a Set<T, H> {
     type IntoIter = Iter<'a, T>;
     type Item = &'a T;
     fn into_iter(self) -> Iter<'a, T> {
@@ -63,34 +330,263 @@ impl<T> Iterator for IntoIter<T> {
     fn next(&mut self) -> Option<Self::Item> {
         self.inner.next()
     }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<T> DoubleEndedIterator for IntoIter<T> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.inner.next_back()
+    }
+}
+
+impl<T> ExactSizeIterator for IntoIter<T> { }
+
+impl<T> FusedIterator for IntoIter<T> { }
+
+pub struct Drain<'a, T> {
+    inner: crate::skiplist::Drain<'a, T>,
+}
+
+impl<'a, T> Iterator for Drain<'a, T> {
+    type Item = T;
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<'a, T> DoubleEndedIterator for Drain<'a, T> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.inner.next_back()
+    }
+}
+
+impl<'a, T> ExactSizeIterator for Drain<'a, T> { }
+
+impl<'a, T> FusedIterator for Drain<'a, T> { }
+
+pub struct ExtractIf<'a, T, F, const H: usize = MAX_HEIGHT> {
+    inner: crate::skiplist::ExtractIf<'a, T, F, H>,
+}
+
+impl<'a, T, F: FnMut(&T) -> bool, const H: usize> Iterator for ExtractIf<'a, T, F, H> {
+    type Item = T;
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next()
+    }
 }
 
+impl<'a, T, F: FnMut(&T) -> bool, const H: usize> FusedIterator for ExtractIf<'a, T, F, H> { }
+
+// Iter is not an ExactSizeIterator: other threads may insert into the set
+// while it is being iterated.
+
 pub struct Iter<'a, T> {
     inner: Elems<'a, T>,
 }
 
+impl<'a, T> Clone for Iter<'a, T> {
+    fn clone(&self) -> Iter<'a, T> {
+        Iter { inner: self.inner.clone() }
+    }
+}
+
+// The set is in order, so the greatest element is the last and the least is
+// the first, and the last is found without walking the set.
 impl<'a, T: 'a> Iterator for Iter<'a, T> {
     type Item = &'a T;
     fn next(&mut self) -> Option<Self::Item> {
         self.inner.next()
     }
+
+    fn last(self) -> Option<Self::Item> {
+        self.inner.last()
+    }
+
+    fn max(self) -> Option<Self::Item> where Self::Item: Ord {
+        self.last()
+    }
+
+    fn min(mut self) -> Option<Self::Item> where Self::Item: Ord {
+        self.next()
+    }
+}
+
+impl<'a, T: 'a> FusedIterator for Iter<'a, T> { }
+
+// Merge walks two sets in order at the same time, yielding the next value from
+// either set, or from both if they are equal. The set operations are built on
+// it, so each takes time linear in the size of the sets. It merges either
+// borrowed or owned values, depending on how the sets are iterated.
+struct Merge<I: Iterator> {
+    a: Peekable<I>,
+    b: Peekable<I>,
+}
+
+impl<'a, T: Ord> Merge<Iter<'a, T>> {
+    fn new<const H: usize>(a: &'a Set<T, H>, b: &'a Set<T, H>) -> Merge<Iter<'a, T>> {
+        Merge { a: a.iter().peekable(), b: b.iter().peekable() }
+    }
+}
+
+impl<I: Iterator> Merge<I> where I::Item: Ord {
+    fn next(&mut self) -> (Option<I::Item>, Option<I::Item>) {
+        let order = match (self.a.peek(), self.b.peek()) {
+            (Some(x), Some(y))  => x.cmp(y),
+            (Some(_), None)     => Ordering::Less,
+            (None, Some(_))     => Ordering::Greater,
+            (None, None)        => return (None, None),
+        };
+
+        match order {
+            Ordering::Less      => (self.a.next(), None),
+            Ordering::Greater   => (None, self.b.next()),
+            Ordering::Equal     => (self.a.next(), self.b.next()),
+        }
+    }
+}
+
+pub struct Difference<'a, T> {
+    merge: Merge<Iter<'a, T>>,
+}
+
+impl<'a, T: Ord> Iterator for Difference<'a, T> {
+    type Item = &'a T;
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            match self.merge.next() {
+                (Some(x), None) => return Some(x),
+                (None, None)    => return None,
+                _               => { }
+            }
+        }
+    }
+}
+
+impl<'a, T: Ord> FusedIterator for Difference<'a, T> { }
+
+pub struct SymmetricDifference<'a, T> {
+    merge: Merge<Iter<'a, T>>,
+}
+
+impl<'a, T: Ord> Iterator for SymmetricDifference<'a, T> {
+    type Item = &'a T;
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            match self.merge.next() {
+                (Some(x), None) | (None, Some(x))   => return Some(x),
+                (None, None)                        => return None,
+                (Some(_), Some(_))                  => { }
+            }
+        }
+    }
+}
+
+impl<'a, T: Ord> FusedIterator for SymmetricDifference<'a, T> { }
+
+pub struct Intersection<'a, T> {
+    merge: Merge<Iter<'a, T>>,
+}
+
+impl<'a, T: Ord> Iterator for Intersection<'a, T> {
+    type Item = &'a T;
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            match self.merge.next() {
+                (Some(x), Some(_))  => return Some(x),
+                (None, None)        => return None,
+                _                   => { }
+            }
+        }
+    }
+}
+
+impl<'a, T: Ord> FusedIterator for Intersection<'a, T> { }
+
+pub struct Union<'a, T> {
+    merge: Merge<Iter<'a, T>>,
 }
 
-impl<T: Ord> Extend<T> for Set<T> {
+impl<'a, T: Ord> Iterator for Union<'a, T> {
+    type Item = &'a T;
+    fn next(&mut self) -> Option<Self::Item> {
+        let (x, y) = self.merge.next();
+        x.or(y)
+    }
+}
+
+impl<'a, T: Ord> FusedIterator for Union<'a, T> { }
+
+// The set operators collect the values of the corresponding iterator, which
+// are already in order, into a new set.
+impl<T: Ord + Clone, const H: usize> BitOr<&Set<T, H>> for &Set<T, H> {
+    type Output = Set<T, H>;
+    fn bitor(self, rhs: &Set<T, H>) -> Set<T, H> {
+        Set { inner: SkipList::from_sorted_iter(self.union(rhs).cloned()) }
+    }
+}
+
+impl<T: Ord + Clone, const H: usize> BitAnd<&Set<T, H>> for &Set<T, H> {
+    type Output = Set<T, H>;
+    fn bitand(self, rhs: &Set<T, H>) -> Set<T, H> {
+        Set { inner: SkipList::from_sorted_iter(self.intersection(rhs).cloned()) }
+    }
+}
+
+impl<T: Ord + Clone, const H: usize> BitXor<&Set<T, H>> for &Set<T, H> {
+    type Output = Set<T, H>;
+    fn bitxor(self, rhs: &Set<T, H>) -> Set<T, H> {
+        Set { inner: SkipList::from_sorted_iter(self.symmetric_difference(rhs).cloned()) }
+    }
+}
+
+impl<T: Ord + Clone, const H: usize> Sub<&Set<T, H>> for &Set<T, H> {
+    type Output = Set<T, H>;
+    fn sub(self, rhs: &Set<T, H>) -> Set<T, H> {
+        Set { inner: SkipList::from_sorted_iter(self.difference(rhs).cloned()) }
+    }
+}
+
+impl<T: Ord, const H: usize> Extend<T> for Set<T, H> {
     fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
         self.inner.extend(iter);
     }
 }
 
-impl<'a, T: 'a + Ord + Copy> Extend<&'a T> for Set<T> {
+impl<'a, T: 'a + Ord + Copy, const H: usize> Extend<&'a T> for Set<T, H> {
     fn extend<I: IntoIterator<Item = &'a T>>(&mut self, iter: I) {
         self.inner.extend(iter);
     }
 }
 
-impl<T: Ord> FromIterator<T> for Set<T> {
+impl<T: Ord, const N: usize> From<[T; N]> for Set<T> {
+    fn from(values: [T; N]) -> Self {
+        Set::from_iter(values)
+    }
+}
+
+// A BTreeSet is already sorted, so the list is built without searching.
+impl<T: Ord> From<BTreeSet<T>> for Set<T> {
+    fn from(set: BTreeSet<T>) -> Self {
+        Set { inner: SkipList::from_sorted_iter(set) }
+    }
+}
+
+impl<T: Ord, const H: usize> From<Set<T, H>> for BTreeSet<T> {
+    fn from(set: Set<T, H>) -> Self {
+        set.into_iter().collect()
+    }
+}
+
+impl<T: Ord, const H: usize> FromIterator<T> for Set<T, H> {
     fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
-        let mut set = Self::new();
+        let mut set = Self::with_height();
         set.extend(iter);
         set
     }
@@ -102,3 +598,127 @@ fn test_collect() {
     let set: Set<_> = range.clone().collect();
     range.for_each(|i| assert!(set.contains(&i)));
 }
+
+#[cfg(feature = "std")]
+#[test]
+fn test_iter_from() {
+    let set: Set<String> = ["apple", "banana", "cherry"].iter().map(|s| s.to_string()).collect();
+    assert!(set.iter_from("b").eq(["banana", "cherry"]));
+    assert!(set.iter_from("banana").eq(["banana", "cherry"]));
+    assert_eq!(set.iter_from("d").next(), None);
+}
+
+#[test]
+fn test_btree_set() {
+    let btree: BTreeSet<i32> = (0..100).rev().collect();
+    let set = Set::from(btree.clone());
+    assert!(set.iter().eq(btree.iter()));
+    assert!(set.contains(&50));
+    assert_eq!(BTreeSet::from(set), btree);
+}
+
+#[test]
+fn test_iter_last() {
+    let set: Set<_> = (0..1000).collect();
+    assert_eq!(set.iter().last(), Some(&999));
+    assert_eq!(set.iter().max(), Some(&999));
+    assert_eq!(set.iter().min(), Some(&0));
+    assert_eq!(set.iter_from(&1000).max(), None);
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn test_into_iter_len() {
+    let set: Set<_> = (0..10).collect();
+    let mut iter = set.into_iter();
+    assert_eq!(iter.len(), 10);
+    iter.next();
+    assert_eq!(iter.len(), 9);
+    assert_eq!(iter.collect::<Vec<_>>(), (1..10).collect::<Vec<_>>());
+}
+
+#[test]
+fn test_eq() {
+    let a: Set<_> = (0..10).collect();
+    let b: Set<_> = (0..10).rev().collect();
+    assert_eq!(a, b);
+    b.insert(10);
+    assert_ne!(a, b);
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn test_hash() {
+    use std::collections::hash_map::DefaultHasher;
+
+    fn hash<T: Hash>(value: &T) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        value.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    let a: Set<_> = (0..10).collect();
+    let b: Set<_> = vec![3, 1, 4, 0, 5, 9, 2, 6, 8, 7].into_iter().collect();
+    assert!(a == b);
+    assert_eq!(hash(&a), hash(&b));
+}
+
+#[test]
+fn test_from_array() {
+    let set = Set::from([3, 1, 2, 1]);
+    assert_eq!(set.len(), 3);
+    assert!(set.iter().copied().eq(1..=3));
+}
+
+#[test]
+fn test_subset_disjoint() {
+    let a = Set::from([1, 3, 5]);
+    let b = Set::from([1, 2, 3, 4, 5]);
+    let c = Set::from([2, 4, 6]);
+    assert!(a.is_subset(&b));
+    assert!(!b.is_subset(&a));
+    assert!(b.is_superset(&a));
+    assert!(Set::new().is_subset(&a));
+    assert!(a.is_disjoint(&c));
+    assert!(!b.is_disjoint(&c));
+    assert!(!Set::from([6]).is_subset(&b));
+}
+
+#[test]
+fn test_operators() {
+    let a = Set::from([1, 2, 3, 4]);
+    let b = Set::from([3, 4, 5, 6]);
+    assert!((&a | &b).iter().copied().eq(1..=6));
+    assert!((&a & &b).iter().copied().eq(3..=4));
+    assert!((&a ^ &b).iter().copied().eq([1, 2, 5, 6].iter().copied()));
+    assert!((&a - &b).iter().copied().eq(1..=2));
+    assert!((&b - &a).iter().copied().eq(5..=6));
+    assert_eq!((&a - &a).len(), 0);
+}
+
+#[test]
+fn test_into_operations() {
+    use alloc::string::{String, ToString};
+
+    // Strings are not Copy, so the values must be moved rather than cloned.
+    let set = |xs: &[i32]| xs.iter().map(|x| x.to_string()).collect::<Set<String>>();
+    let (a, b) = (set(&[1, 2, 3, 4]), set(&[3, 4, 5, 6]));
+    assert_eq!(a.clone().into_union(b.clone()), set(&[1, 2, 3, 4, 5, 6]));
+    assert_eq!(a.clone().into_intersection(b.clone()), set(&[3, 4]));
+    assert_eq!(b.clone().into_difference(a.clone()), set(&[5, 6]));
+    assert_eq!(a.into_difference(b).len(), 2);
+    assert_eq!(Set::<String>::new().into_union(set(&[7])), set(&[7]));
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn test_debug() {
+    assert_eq!(format!("{:?}", Set::from([3, 1, 2])), "{1, 2, 3}");
+    assert_eq!(format!("{:?}", Set::<i32>::new()), "{}");
+}
+
+#[test]
+fn test_range_count() {
+    let set: Set<_> = (0..100).collect();
+    assert_eq!(set.range_count(10..20), 10);
+}
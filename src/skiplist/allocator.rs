@@ -0,0 +1,216 @@
+use alloc::alloc::{alloc_zeroed, dealloc, Layout};
+#[cfg(feature = "std")]
+use alloc::vec::Vec;
+use core::ptr::NonNull;
+#[cfg(feature = "std")]
+use std::sync::Mutex;
+
+// The source of the memory for the nodes of a list. This mirrors the unstable
+// `std::alloc::Allocator` trait, so that lists can allocate from arenas and
+// other custom allocators on stable.
+//
+// The trait is unsafe to implement because lists trust the memory it returns:
+// memory returned by `allocate_zeroed` must be zeroed, valid for the layout,
+// and remain valid until it is passed to `deallocate` on this allocator or a
+// clone of it. Nodes moved into a new list by `split_off` are deallocated by a
+// clone of the original list's allocator.
+#[allow(clippy::missing_safety_doc)] // the contract is stated above, in prose
+pub unsafe trait Allocator {
+    fn allocate_zeroed(&self, layout: Layout) -> Option<NonNull<u8>>;
+
+    // Callers must only pass a pointer allocated by this allocator, or a clone
+    // of it, with the same layout.
+    unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout);
+}
+
+// The global allocator, which lists use by default.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Global;
+
+unsafe impl Allocator for Global {
+    fn allocate_zeroed(&self, layout: Layout) -> Option<NonNull<u8>> {
+        NonNull::new(unsafe { alloc_zeroed(layout) })
+    }
+
+    unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+        dealloc(ptr.as_ptr(), layout)
+    }
+}
+
+unsafe impl<A: Allocator + ?Sized> Allocator for &A {
+    fn allocate_zeroed(&self, layout: Layout) -> Option<NonNull<u8>> {
+        (**self).allocate_zeroed(layout)
+    }
+
+    unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+        (**self).deallocate(ptr, layout)
+    }
+}
+
+// An allocator which keeps the memory of the nodes deallocated through it, and
+// reuses it for later nodes of the same layout, rather than returning it to
+// the allocator it wraps. The layout of a node depends only on its height, so
+// each height has its own list of free nodes. This trades memory for speed in
+// lists which insert and remove at similar rates: the memory is only returned
+// when the Recycling allocator itself is dropped.
+//
+// A list only deallocates nodes when it is held exclusively, so only removals
+// through `&mut` (take, pop_first and pop_last, clear, retain, drain, or
+// dropping the list) return nodes to be reused. Nodes removed through a shared
+// reference are retired, and are not recycled until one of those next runs.
+//
+// Freed nodes are kept behind a lock rather than in a lock-free stack, because
+// popping from a lock-free stack concurrently is subject to the ABA problem.
+// The lock is only held to push or pop a single pointer.
+#[cfg(feature = "std")]
+pub struct Recycling<A: Allocator = Global> {
+    alloc: A,
+    free: Mutex<Vec<(Layout, Vec<NonNull<u8>>)>>,
+}
+
+#[cfg(feature = "std")]
+impl<A: Allocator> Recycling<A> {
+    pub fn new(alloc: A) -> Recycling<A> {
+        Recycling { alloc, free: Mutex::new(Vec::new()) }
+    }
+}
+
+#[cfg(feature = "std")]
+impl Default for Recycling {
+    fn default() -> Recycling {
+        Recycling::new(Global)
+    }
+}
+
+#[cfg(feature = "std")]
+unsafe impl<A: Allocator> Allocator for Recycling<A> {
+    fn allocate_zeroed(&self, layout: Layout) -> Option<NonNull<u8>> {
+        let recycled = {
+            let mut free = self.free.lock().unwrap_or_else(|err| err.into_inner());
+            free.iter_mut().find(|(l, _)| *l == layout).and_then(|(_, ptrs)| ptrs.pop())
+        };
+        match recycled {
+            Some(ptr)   => unsafe {
+                ptr.as_ptr().write_bytes(0, layout.size());
+                Some(ptr)
+            }
+            None        => self.alloc.allocate_zeroed(layout),
+        }
+    }
+
+    unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+        let mut free = self.free.lock().unwrap_or_else(|err| err.into_inner());
+        match free.iter_mut().find(|(l, _)| *l == layout) {
+            Some((_, ptrs)) => ptrs.push(ptr),
+            None            => free.push((layout, vec![ptr])),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl<A: Allocator> Drop for Recycling<A> {
+    fn drop(&mut self) {
+        let free = self.free.get_mut().unwrap_or_else(|err| err.into_inner());
+        for (layout, ptrs) in free.drain(..) {
+            for ptr in ptrs {
+                unsafe { self.alloc.deallocate(ptr, layout) }
+            }
+        }
+    }
+}
+
+// The free nodes are only accessed with the lock held.
+#[cfg(feature = "std")]
+unsafe impl<A: Allocator + Send> Send for Recycling<A> { }
+#[cfg(feature = "std")]
+unsafe impl<A: Allocator + Sync> Sync for Recycling<A> { }
+
+#[cfg(feature = "std")]
+#[test]
+fn test_allocator() {
+    use std::cell::Cell;
+    use super::{SkipList, MAX_HEIGHT};
+
+    // Counts the nodes it has allocated which have not been deallocated.
+    #[derive(Default)]
+    struct Counting(Cell<usize>);
+
+    unsafe impl Allocator for Counting {
+        fn allocate_zeroed(&self, layout: Layout) -> Option<NonNull<u8>> {
+            self.0.set(self.0.get() + 1);
+            Global.allocate_zeroed(layout)
+        }
+
+        unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+            self.0.set(self.0.get() - 1);
+            Global.deallocate(ptr, layout)
+        }
+    }
+
+    let (a, b) = (Counting::default(), Counting::default());
+    let mut list: SkipList<i32, MAX_HEIGHT, &Counting> = SkipList::new_in(&a);
+    list.extend(0..10);
+    assert_eq!(list.take(&3), Some(3));
+    assert_eq!(a.0.get(), 9);
+
+    let mut other = SkipList::new_in(&b);
+    other.extend(5..15);
+    list.append(&mut other);
+    assert_eq!(b.0.get(), 0);
+    assert_eq!(a.0.get(), 14);
+    assert!(list.elems().copied().eq((0..15).filter(|&x| x != 3)));
+
+    let split = list.split_off(&10);
+    let mut elems = split.into_elems();
+    assert_eq!(elems.next(), Some(10));
+    drop(elems);
+    assert_eq!(a.0.get(), 9);
+
+    drop(list);
+    assert_eq!(a.0.get(), 0);
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn test_recycling() {
+    use super::SkipList;
+
+    let free = |recycling: &Recycling| -> usize {
+        recycling.free.lock().unwrap().iter().map(|(_, ptrs)| ptrs.len()).sum()
+    };
+
+    // Every node of a list with a single lane has the same layout.
+    let recycling = Recycling::default();
+    let mut list: SkipList<i32, 1, &Recycling> = SkipList::new_in(&recycling);
+    list.extend(0..100);
+    list.clear();
+    assert_eq!(free(&recycling), 100);
+    list.extend(0..100);
+    assert_eq!(free(&recycling), 0);
+    assert!(list.elems().copied().eq(0..100));
+
+    // Nodes are reused after take and retain, but not after a shared remove
+    // until the list is next held exclusively. Retiring a node also allocates
+    // an entry for it, which here has the same layout as a node, so it reuses
+    // a free node and is recycled along with the node it retired.
+    assert_eq!(list.take(&0), Some(0));
+    list.retain(|&x| x >= 10);
+    assert_eq!(free(&recycling), 10);
+    list.extend(0..5);
+    assert_eq!(free(&recycling), 5);
+    list.remove(&50);
+    assert_eq!(free(&recycling), 4);
+    list.retain(|_| true);
+    assert_eq!(free(&recycling), 6);
+    list.extend(50..51);
+    assert_eq!(free(&recycling), 5);
+    assert!(list.elems().copied().eq((0..5).chain(10..100)));
+
+    // The other list reuses the five free nodes.
+    let mut other: SkipList<i32, 1, &Recycling> = SkipList::new_in(&recycling);
+    other.extend(100..200);
+    assert_eq!(free(&recycling), 0);
+    drop(list);
+    drop(other);
+    assert_eq!(free(&recycling), 195);
+}
@@ -0,0 +1,38 @@
+use core::array;
+use core::ptr::{self, NonNull};
+use core::sync::atomic::Ordering::Relaxed;
+
+use crate::sync::AtomicPtr;
+use super::Node;
+
+// When building a list in order with exclusive access to it, we can simply
+// link each node onto the end of the list, in every lane it has. To do this we
+// track the last lane pointer in each lane, which is where the next node in
+// that lane should be linked.
+pub(super) struct Tails<T, const H: usize> {
+    preds: [*const AtomicPtr<Node<T>>; H],
+}
+
+impl<T, const H: usize> Tails<T, H> {
+    // The lanes are the head lanes of a list to which we have exclusive access.
+    pub(super) fn new(lanes: &[AtomicPtr<Node<T>>; H]) -> Tails<T, H> {
+        Tails {
+            preds: array::from_fn(|level| &lanes[H - 1 - level] as *const AtomicPtr<Node<T>>),
+        }
+    }
+
+    pub(super) fn push(&mut self, node: NonNull<Node<T>>) {
+        let lanes = unsafe { node.as_ref().lanes() };
+        for (level, lane) in lanes.iter().rev().enumerate() {
+            unsafe { (*self.preds[level]).store(node.as_ptr(), Relaxed) }
+            self.preds[level] = lane;
+        }
+    }
+
+    // Terminate every lane after the last node pushed onto it.
+    pub(super) fn finish(&self) {
+        for pred in self.preds {
+            unsafe { (*pred).store(ptr::null_mut(), Relaxed) }
+        }
+    }
+}
@@ -0,0 +1,92 @@
+use core::cmp::Ordering;
+
+use crate::ord::Comparing;
+use super::{get, insert, remove, Elems, Global, Heights, SkipList, MAX_HEIGHT};
+
+// A SkipListBy is a skiplist ordered by a comparator function chosen at
+// runtime, rather than by the AbstractOrd implementation of its elements. The
+// comparator must be a total order, just like an Ord implementation, and it is
+// used for every operation which searches the list.
+pub struct SkipListBy<T, F, const H: usize = MAX_HEIGHT> {
+    list: SkipList<T, H>,
+    cmp: F,
+}
+
+impl<T, F: Fn(&T, &T) -> Ordering> SkipListBy<T, F> {
+    pub fn with_comparator(cmp: F) -> SkipListBy<T, F> {
+        SkipListBy { list: SkipList::unordered(), cmp }
+    }
+}
+
+// Other front-ends ordered by a comparator, like MapBy, hold the comparator
+// beside a list whose elements need not be ordered themselves.
+impl<T, const H: usize> SkipList<T, H> {
+    pub(crate) fn unordered() -> SkipList<T, H> {
+        SkipList::with_heights(Heights::Random, Global)
+    }
+
+    pub(crate) fn insert_by<F: Fn(&T, &T) -> Ordering>(&self, elem: T, cmp: F) -> Result<&T, (T, &T)> {
+        insert::insert_by(self, elem, cmp)
+    }
+}
+
+impl<T, F: Fn(&T, &T) -> Ordering, const H: usize> SkipListBy<T, F, H> {
+    pub fn insert(&self, elem: T) -> Option<(T, &T)> {
+        insert::insert_by(&self.list, elem, &self.cmp).err()
+    }
+
+    pub fn get(&self, elem: &T) -> Option<&T> {
+        get::get(self.list.lanes(), &self.key(elem)).map(|node| unsafe { &(*node.as_ptr()).inner.elem })
+    }
+
+    pub fn contains(&self, elem: &T) -> bool {
+        self.get(elem).is_some()
+    }
+
+    pub fn remove(&self, elem: &T) -> Option<&T> {
+        remove::remove(&self.list, &self.key(elem))
+    }
+
+    pub fn first(&self) -> Option<&T> {
+        self.list.first()
+    }
+
+    pub fn len(&self) -> usize {
+        self.list.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.list.is_empty()
+    }
+
+    pub fn elems(&self) -> Elems<'_, T> {
+        self.list.elems()
+    }
+
+    fn key<'a>(&'a self, elem: &'a T) -> Comparing<'a, T, F> {
+        Comparing { elem, cmp: &self.cmp }
+    }
+}
+
+#[test]
+fn test_comparator() {
+    let list = SkipListBy::with_comparator(|x: &i32, y: &i32| y.cmp(x));
+    list.insert(1);
+    list.insert(3);
+    list.insert(2);
+    assert!(list.insert(2).is_some());
+    assert!(list.elems().copied().eq([3, 2, 1]));
+    assert_eq!(list.first(), Some(&3));
+    assert_eq!(list.get(&2), Some(&2));
+    assert_eq!(list.remove(&3), Some(&3));
+    assert!(!list.contains(&3));
+    assert_eq!(list.len(), 2);
+
+    let table = ['b', 'a', 'c'];
+    let rank = move |c: &char| table.iter().position(|x| x == c);
+    let list = SkipListBy::with_comparator(move |x: &char, y: &char| rank(x).cmp(&rank(y)));
+    list.insert('a');
+    list.insert('c');
+    list.insert('b');
+    assert!(list.elems().copied().eq(['b', 'a', 'c']));
+}
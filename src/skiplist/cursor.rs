@@ -0,0 +1,74 @@
+use crate::AbstractOrd;
+use super::{get, Allocator, Global, Node, Nodes, SkipList, MAX_HEIGHT};
+
+// A Cursor holds a position in the lowest lane of the list. The current
+// element is one which had not been removed when the cursor moved to it; if it
+// is removed afterward the cursor still refers to it, and moving the cursor
+// moves past it to the next element which has not been removed.
+//
+// Once the cursor has moved past the end of the list it stays there, even if
+// other threads append elements, until it is seeked again.
+pub struct Cursor<'a, T, const H: usize = MAX_HEIGHT, A: Allocator = Global> {
+    list: &'a SkipList<T, H, A>,
+    node: Option<&'a Node<T>>,
+}
+
+impl<'a, T, const H: usize, A: Allocator> Cursor<'a, T, H, A> {
+    pub(super) fn new<U>(list: &'a SkipList<T, H, A>, elem: &U) -> Cursor<'a, T, H, A>
+    where
+        U: AbstractOrd<T> + ?Sized,
+    {
+        let mut cursor = Cursor { list, node: None };
+        cursor.seek(elem);
+        cursor
+    }
+
+    pub fn current(&self) -> Option<&'a T> {
+        self.node.map(|node| &node.inner.elem)
+    }
+
+    pub fn peek_next(&self) -> Option<&'a T> {
+        self.next_node().map(|node| &node.inner.elem)
+    }
+
+    pub fn move_next(&mut self) {
+        self.node = self.next_node();
+    }
+
+    // Move the cursor to the first element greater than or equal to `elem`,
+    // which may be before or after its current position.
+    pub fn seek<U>(&mut self, elem: &U)
+    where
+        U: AbstractOrd<T> + ?Sized,
+    {
+        self.node = Nodes::new(get::seek(self.list.lanes(), elem, true)).next();
+    }
+
+    fn next_node(&self) -> Option<&'a Node<T>> {
+        Nodes::new(self.node?.next()).next()
+    }
+}
+
+#[test]
+fn test_cursor() {
+    let list: SkipList<i32> = (0..10).map(|x| x * 2).collect();
+
+    let mut cursor = list.cursor_at(&5);
+    assert_eq!(cursor.current(), Some(&6));
+    assert_eq!(cursor.peek_next(), Some(&8));
+    assert_eq!(cursor.current(), Some(&6));
+
+    list.remove(&8);
+    cursor.move_next();
+    assert_eq!(cursor.current(), Some(&10));
+
+    cursor.seek(&0);
+    assert_eq!(cursor.current(), Some(&0));
+
+    cursor.seek(&18);
+    assert_eq!(cursor.peek_next(), None);
+    cursor.move_next();
+    assert_eq!(cursor.current(), None);
+    cursor.move_next();
+    assert_eq!(cursor.current(), None);
+}
@@ -0,0 +1,96 @@
+use core::cmp::Ordering::*;
+use core::marker::PhantomData;
+use core::ptr;
+use core::sync::atomic::Ordering::Acquire;
+
+use crate::AbstractOrd;
+use super::insert::Start;
+use super::{unmark, Allocator, Node, Ptr, SkipList, MAX_HEIGHT};
+
+// A finger remembers, in each lane, the predecessor of the last element
+// searched for with it, so that a search for a nearby element can begin from
+// there instead of descending from the head of the list. When elements are
+// searched for in nearly sorted order, most searches only walk a few nodes.
+//
+// The finger borrows the list for as long as it is used, so the nodes it
+// remembers cannot be deallocated. It also remembers which list it was last
+// used with, and forgets its predecessors if it is used with another.
+pub struct Finger<'a, T, const H: usize = MAX_HEIGHT> {
+    list: *const (),
+    pub(super) preds: [Ptr<Node<T>>; H],
+    _marker: PhantomData<&'a Node<T>>,
+}
+
+impl<'a, T, const H: usize> Finger<'a, T, H> {
+    pub fn new() -> Finger<'a, T, H> {
+        Finger { list: ptr::null(), preds: [None; H], _marker: PhantomData }
+    }
+
+    // Find where to begin searching for `elem`: the lowest lane in which the
+    // remembered predecessor precedes `elem` and its successor does not. A
+    // predecessor which has since been removed cannot begin a search, so we
+    // move up to the next lane. If no lane will do, we begin at the head.
+    pub(super) fn start<U, A>(&mut self, list: &'a SkipList<T, H, A>, elem: &U) -> Start<'a, T>
+    where
+        U: AbstractOrd<T> + ?Sized,
+        A: Allocator,
+    {
+        let head = &list.lanes[..];
+        let addr = list as *const SkipList<T, H, A> as *const ();
+        if self.list != addr {
+            self.list = addr;
+            self.preds = [None; H];
+            return Start { node: None, lanes: head };
+        }
+
+        for height in 1..=H {
+            let node: Option<&'a Node<T>> = self.preds[height - 1].map(|ptr| unsafe { &*ptr.as_ptr() });
+            let lanes = match node {
+                Some(node) if node.is_removed() || elem.cmp(&node.inner.elem) != Greater => continue,
+                Some(node)  => &node.lanes()[(node.height() - height)..],
+                None        => &head[(H - height)..],
+            };
+
+            match unsafe { unmark(lanes[0].load(Acquire)).as_ref() } {
+                Some(succ) if elem.cmp(&succ.inner.elem) == Greater => continue,
+                _ => return Start { node, lanes },
+            }
+        }
+
+        Start { node: None, lanes: head }
+    }
+}
+
+impl<'a, T, const H: usize> Default for Finger<'a, T, H> {
+    fn default() -> Finger<'a, T, H> {
+        Finger::new()
+    }
+}
+
+#[test]
+fn test_finger() {
+    let list: SkipList<i32> = SkipList::new();
+    let mut finger = Finger::new();
+    for x in (0..200).filter(|x| x % 3 != 0) {
+        assert!(list.insert_with_finger(x, &mut finger).is_none());
+    }
+    for x in (0..200).filter(|x| x % 3 == 0).rev() {
+        assert!(list.insert_with_finger(x, &mut finger).is_none());
+    }
+    assert_eq!(list.insert_with_finger(100, &mut finger), Some((100, &100)));
+    assert!(list.elems().copied().eq(0..200));
+    assert_eq!(list.len(), 200);
+
+    list.remove(&51);
+    let mut finger = Finger::new();
+    for x in 0..200 {
+        let expected = if x == 51 { None } else { Some(&x) };
+        assert_eq!(list.get_with_finger(&x, &mut finger), expected);
+    }
+    assert_eq!(list.get_with_finger(&7, &mut finger), Some(&7));
+
+    // A finger used with another list starts over from its head.
+    let other: SkipList<i32> = (0..10).collect();
+    assert_eq!(other.get_with_finger(&5, &mut finger), Some(&5));
+    assert_eq!(list.get_with_finger(&150, &mut finger), Some(&150));
+}
@@ -1,35 +1,75 @@
-use std::cmp::Ordering::*;
-use std::ptr::NonNull;
-use std::sync::atomic::AtomicPtr;
-use std::sync::atomic::Ordering::Acquire;
+use core::cmp::Ordering::*;
+use core::ptr::NonNull;
+use core::sync::atomic::Ordering::Acquire;
 
 use crate::AbstractOrd;
-use super::{Node, Ptr};
+use crate::sync::AtomicPtr;
+use super::{Node, Ptr, unmark};
 
-pub(super) fn get<'a, T, U>(mut lanes: &'a [AtomicPtr<Node<T>>], elem: &U) -> Option<&'a T>
+// Unlike insert and remove, get never writes to the list: it reads through any
+// removed nodes it comes across rather than unlinking them, so that lookup
+// remains wait-free. Removed nodes are not freed while the list is shared, so
+// this is always safe.
+//
+// This returns a pointer to the node, rather than a reference, so that callers
+// holding the list mutably can mutate the element through it.
+pub(super) fn get<T, U>(lanes: &[AtomicPtr<Node<T>>], elem: &U) -> Ptr<Node<T>>
+    where U: AbstractOrd<T> + ?Sized
+{
+    descend(lanes, elem, Target::Equal)
+}
+
+// Find the first node whose element is greater than `elem`, or greater than or
+// equal to it if `inclusive` is set.
+pub(super) fn seek<T, U>(lanes: &[AtomicPtr<Node<T>>], elem: &U, inclusive: bool) -> Ptr<Node<T>>
+    where U: AbstractOrd<T> + ?Sized
+{
+    descend(lanes, elem, if inclusive { Target::AtLeast } else { Target::Above })
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Target {
+    Equal,
+    AtLeast,
+    Above,
+}
+
+// The descent shared by get and seek. We move across while the element in the
+// next node is less than `elem` (or equal to it, when seeking past it), and
+// down otherwise, remembering the successor in each lane; the successor in the
+// lowest lane is the first node at or past `elem`. When looking for an equal
+// element, we stop as soon as we find one, in whichever lane.
+fn descend<T, U>(mut lanes: &[AtomicPtr<Node<T>>], elem: &U, target: Target) -> Ptr<Node<T>>
     where U: AbstractOrd<T> + ?Sized
 {
     let mut height = lanes.len();
+    let mut succ = None;
 
     'across: while height > 0 {
         'down: for atomic_ptr in lanes {
-            let ptr: Ptr<Node<T>> = NonNull::new(atomic_ptr.load(Acquire));
+            let ptr: Ptr<Node<T>> = NonNull::new(unmark(atomic_ptr.load(Acquire)));
 
             match ptr {
                 None        => {
                     height -= 1;
+                    succ = None;
                     continue 'down;
                 }
                 Some(ptr)  => {
-                    let node: &'a Node<T> = unsafe { &*ptr.as_ptr() };
+                    let node: &Node<T> = unsafe { &*ptr.as_ptr() };
 
                     match elem.cmp(&node.inner.elem) {
-                        Equal   => return Some(&node.inner.elem),
-                        Less    => {
+                        Equal if target == Target::Equal && !node.is_removed() => return Some(ptr),
+                        Equal if target == Target::Above => {
+                            lanes = &node.lanes()[(node.height() - height)..];
+                            continue 'across;
+                        }
+                        Less | Equal    => {
                             height -= 1;
+                            succ = Some(ptr);
                             continue 'down;
                         }
-                        Greater => {
+                        Greater         => {
                             lanes = &node.lanes()[(node.height() - height)..];
                             continue 'across;
                         }
@@ -39,5 +79,69 @@ pub(super) fn get<'a, T, U>(mut lanes: &'a [AtomicPtr<Node<T>>], elem: &U) -> Op
         }
     }
 
-    return None;
+    match target {
+        Target::Equal   => None,
+        _               => succ,
+    }
+}
+
+// Find the last node in the list. This is the descent of last_before without
+// a bound, which moves across whenever there is a next node, so it needs no
+// comparisons.
+pub(super) fn last<T>(mut lanes: &[AtomicPtr<Node<T>>]) -> Ptr<Node<T>> {
+    let mut height = lanes.len();
+    let mut last = None;
+
+    'across: while height > 0 {
+        for atomic_ptr in lanes {
+            if let Some(ptr) = NonNull::new(unmark(atomic_ptr.load(Acquire))) {
+                let node: &Node<T> = unsafe { &*ptr.as_ptr() };
+                last = Some(ptr);
+                lanes = &node.lanes()[(node.height() - height)..];
+                continue 'across;
+            }
+            height -= 1;
+        }
+    }
+
+    last
+}
+
+// Find the last node whose element is less than `bound` (or equal to it, if
+// `inclusive` is set), or the last node in the list if there is no bound. Like
+// get, this descends the list, but it moves across whenever the next node is
+// before the bound.
+pub(super) fn last_before<'a, T, U>(mut lanes: &'a [AtomicPtr<Node<T>>], bound: Option<&U>, inclusive: bool)
+    -> Ptr<Node<T>>
+    where U: AbstractOrd<T> + ?Sized
+{
+    let mut height = lanes.len();
+    let mut last = None;
+
+    'across: while height > 0 {
+        'down: for atomic_ptr in lanes {
+            let ptr: Ptr<Node<T>> = NonNull::new(unmark(atomic_ptr.load(Acquire)));
+
+            if let Some(ptr) = ptr {
+                let node: &'a Node<T> = unsafe { &*ptr.as_ptr() };
+
+                let before = bound.is_none_or(|bound| match bound.cmp(&node.inner.elem) {
+                    Greater => true,
+                    Equal   => inclusive,
+                    Less    => false,
+                });
+
+                if before {
+                    last = Some(ptr);
+                    lanes = &node.lanes()[(node.height() - height)..];
+                    continue 'across;
+                }
+            }
+
+            height -= 1;
+            continue 'down;
+        }
+    }
+
+    last
 }
@@ -0,0 +1,244 @@
+#[cfg(feature = "std")]
+use core::cell::Cell;
+use core::cmp;
+use core::sync::atomic::Ordering::Relaxed;
+
+#[cfg(target_has_atomic = "64")]
+use crate::sync::AtomicU64;
+#[cfg(not(target_has_atomic = "64"))]
+use crate::sync::AtomicUsize;
+
+// The source of the heights of newly allocated nodes. By default, heights are
+// drawn from a thread local RNG, seeded by rand, or without the `rand` feature
+// by the random keys std generates for HashMap. Without the `std` feature
+// there are no thread locals or system randomness, so they are drawn from a
+// generator shared by every list, which always begins from the same state;
+// lists built without std should be given a source of random words instead,
+// such as a hardware RNG, with which every height is drawn from that source.
+//
+// A list constructed with a seed instead draws heights from its own generator,
+// so that the same sequence of inserts always produces a list with exactly the
+// same structure.
+pub(super) enum Heights {
+    Random,
+    Seeded(State),
+    Source(fn() -> u64),
+}
+
+// The state of the generators shared between threads. Targets without 64 bit
+// atomics keep it in a usize instead, which gives a shorter period, but the
+// output is still mixed into a full 64 bit word.
+#[cfg(target_has_atomic = "64")]
+pub(super) type State = AtomicU64;
+#[cfg(not(target_has_atomic = "64"))]
+pub(super) type State = AtomicUsize;
+
+impl Clone for Heights {
+    fn clone(&self) -> Heights {
+        match self {
+            Heights::Random         => Heights::Random,
+            Heights::Seeded(state)  => Heights::Seeded(State::new(state.load(Relaxed))),
+            Heights::Source(source) => Heights::Source(*source),
+        }
+    }
+}
+
+impl Heights {
+    pub(super) fn seeded(seed: u64) -> Heights {
+        Heights::Seeded(State::new(seed as _))
+    }
+
+    // Heights are geometrically distributed with p = 1/2: each node is in the
+    // lowest lane, and in each lane above that with half the probability of
+    // the lane below it. The distribution is truncated at `max_height`, which
+    // takes the whole tail: a node is of height k < max_height with
+    // probability 2^-k, and of height max_height with probability
+    // 2^-(max_height - 1), the same as the height below it. This is intended;
+    // a node cannot be in more lanes than the list has, and it is still in
+    // the top lane with half the probability of the one below.
+    pub(super) fn next(&self, max_height: usize) -> usize {
+        self.next_with(max_height, 1)
+    }
+
+    // More generally, with p = 1/2^branching: each lane above the lowest takes
+    // a run of `branching` zero bits rather than one.
+    pub(super) fn next_with(&self, max_height: usize, branching: u32) -> usize {
+        let bits = match self {
+            #[cfg(feature = "std")]
+            Heights::Random if branching == 1   => return THREAD_HEIGHTS.with(|heights| heights.next(max_height)),
+            #[cfg(feature = "std")]
+            Heights::Random                     => return THREAD_HEIGHTS.with(|heights| heights.next_branching(max_height, branching)),
+            #[cfg(not(feature = "std"))]
+            Heights::Random                     => splitmix64(&SHARED_STATE),
+            Heights::Seeded(state)              => splitmix64(state),
+            Heights::Source(source)             => source(),
+        };
+        1 + cmp::min(bits.trailing_zeros() / branching, max_height as u32 - 1) as usize
+    }
+}
+
+// Each thread draws random heights from its own generator, seeded once from
+// the thread RNG, so inserts on different threads share no state.
+//
+// A height is decided by a run of random bits: one zero bit for each lane
+// above the lowest, ended by a one bit or by reaching the maximum height. On
+// average that is two bits, so rather than generating a word for each height,
+// the bits left over are kept and used for the following heights, and a new
+// word is only generated when they run out. This draws heights from exactly
+// the same distribution.
+#[cfg(feature = "std")]
+struct ThreadHeights {
+    state: Cell<u64>,
+    bits: Cell<u64>,
+    len: Cell<u32>,
+}
+
+#[cfg(feature = "std")]
+impl ThreadHeights {
+    fn next(&self, max_height: usize) -> usize {
+        let mut height = 1;
+        loop {
+            if self.len.get() == 0 {
+                self.bits.set(wyrand(&self.state));
+                self.len.set(u64::BITS);
+            }
+
+            let (bits, len) = (self.bits.get(), self.len.get());
+            let zeros = bits.trailing_zeros().min(len);
+            let remaining = (max_height - height) as u32;
+
+            // Either the run reaches the maximum height, or it ends with a one
+            // bit, or it continues past the end of the bits we have left.
+            let (used, done) = match () {
+                _ if zeros >= remaining => (remaining, true),
+                _ if zeros < len        => (zeros + 1, true),
+                _                       => (zeros, false),
+            };
+
+            self.bits.set(bits.checked_shr(used).unwrap_or(0));
+            self.len.set(len - used);
+            height += zeros.min(remaining) as usize;
+            if done { return height; }
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl ThreadHeights {
+    // With more than one bit per lane, the bits are taken a group at a time:
+    // each group of zeros raises the height by one, and any other group ends
+    // it. Bits left over which do not fill a group are discarded.
+    fn next_branching(&self, max_height: usize, branching: u32) -> usize {
+        let mut height = 1;
+        while height < max_height {
+            if self.len.get() < branching {
+                self.bits.set(wyrand(&self.state));
+                self.len.set(u64::BITS);
+            }
+
+            let bits = self.bits.get();
+            self.bits.set(bits >> branching);
+            self.len.set(self.len.get() - branching);
+            if bits & ((1 << branching) - 1) != 0 { break; }
+            height += 1;
+        }
+        height
+    }
+}
+
+#[cfg(feature = "std")]
+std::thread_local! {
+    static THREAD_HEIGHTS: ThreadHeights = ThreadHeights {
+        state: Cell::new(seed()),
+        bits: Cell::new(0),
+        len: Cell::new(0),
+    };
+}
+
+#[cfg(feature = "rand")]
+fn seed() -> u64 {
+    rand::random()
+}
+
+#[cfg(all(feature = "std", not(feature = "rand")))]
+fn seed() -> u64 {
+    use std::collections::hash_map::RandomState;
+    use std::hash::{BuildHasher, Hasher};
+    RandomState::new().build_hasher().finish()
+}
+
+#[cfg(not(feature = "std"))]
+static SHARED_STATE: State = State::new(0);
+
+// Wyrand: one word of state, advanced by a fixed increment and mixed with a
+// single wide multiplication.
+#[cfg(feature = "std")]
+fn wyrand(state: &Cell<u64>) -> u64 {
+    let s = state.get().wrapping_add(0xa076_1d64_78bd_642f);
+    state.set(s);
+    let t = u128::from(s) * u128::from(s ^ 0xe703_7ed1_a0b4_28db);
+    (t >> 64) as u64 ^ t as u64
+}
+
+// SplitMix64: the state is advanced by a fixed increment and then mixed, so
+// that concurrent inserts can share the generator with a single fetch_add.
+// The casts are only needed where the state is a usize.
+#[allow(clippy::unnecessary_cast)]
+fn splitmix64(state: &State) -> u64 {
+    const GAMMA: u64 = 0x9e37_79b9_7f4a_7c15;
+    let mut z = state.fetch_add(GAMMA as _, Relaxed).wrapping_add(GAMMA as _) as u64;
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn test_random_heights() {
+    let heights: Vec<usize> = (0..10_000).map(|_| Heights::Random.next(4)).collect();
+    assert!(heights.iter().all(|&h| (1..=4).contains(&h)));
+    let count = |height| heights.iter().filter(|&&h| h == height).count();
+    assert!((4_000..6_000).contains(&count(1)));
+    assert!((2_000..3_000).contains(&count(2)));
+    assert!((1_000..1_500).contains(&count(3)));
+    assert!((1_000..1_500).contains(&count(4)));
+
+    // Runs of zeros longer than the bits left over continue into the next
+    // word, so the tallest heights are reached.
+    let tall = (0..100_000).map(|_| Heights::Random.next(64)).max().unwrap();
+    assert!(tall >= 12);
+}
+
+#[test]
+fn test_height_distribution() {
+    use super::MAX_HEIGHT;
+
+    const SAMPLES: usize = 1_000_000;
+    let mut counts = [0usize; MAX_HEIGHT + 1];
+    for _ in 0..SAMPLES {
+        counts[Heights::Random.next(MAX_HEIGHT)] += 1;
+    }
+    assert_eq!(counts[0], 0);
+
+    // Each height up to 12 should be within five standard deviations of its
+    // expected count; above that there are too few samples to say much.
+    for (k, &count) in counts.iter().enumerate().take(13).skip(1) {
+        let expected = SAMPLES as f64 / (1u64 << k) as f64;
+        let error = (count as f64 - expected).abs();
+        assert!(error < 5.0 * expected.sqrt(), "height {}: {} samples, expected {}", k, count, expected);
+    }
+    assert!(counts[20..].iter().sum::<usize>() < 10);
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn test_branching_heights() {
+    for heights in [Heights::Random, Heights::seeded(0)] {
+        let heights: Vec<usize> = (0..10_000).map(|_| heights.next_with(3, 2)).collect();
+        assert!(heights.iter().all(|&h| (1..=3).contains(&h)));
+        let count = |height| heights.iter().filter(|&&h| h == height).count();
+        assert!((7_000..8_000).contains(&count(1)));
+        assert!((1_600..2_100).contains(&count(2)));
+        assert!((500..750).contains(&count(3)));
+    }
+}
@@ -1,16 +1,58 @@
-use std::cmp::Ordering::*;
-use std::mem::ManuallyDrop;
-use std::ptr::{self, NonNull};
-use std::sync::atomic::{AtomicPtr, AtomicU8};
-use std::sync::atomic::Ordering::{Acquire, AcqRel, Release};
+use core::cmp::Ordering::{self, *};
+use core::mem::{self, ManuallyDrop};
+use core::ptr::{self, NonNull};
+use core::sync::atomic::Ordering::{Acquire, AcqRel, Relaxed, Release};
 
 use crate::AbstractOrd;
-use super::{Ptr, Node, MAX_HEIGHT};
+use crate::ord::Comparing;
+use crate::sync::{spin_loop, AtomicPtr};
+use super::{Allocator, Finger, Ptr, Node, SkipList, marked, unmark};
 
-pub(super) fn insert<'a, T>(lanes: &'a [AtomicPtr<Node<T>>], elem: T, max_height: &AtomicU8)
-    -> Option<(T, &'a T)>
-where T: AbstractOrd<T>
+// The immediate predecessor and successor of an element in a lane of the
+// skiplist. The predecessor pointer is a pointer to the actual AtomicPtr in
+// that lane of that node, which will be set to point to the new node. The
+// successor pointer is just the address of the successor node, which the new
+// node's pointer will be set to, and which will be used in a compare and swap
+// operation on the predecessor pointer.
+pub(super) type Spot<T> = (*const AtomicPtr<Node<T>>, *mut Node<T>);
+
+pub(super) fn insert<T, const H: usize, A>(list: &SkipList<T, H, A>, elem: T) -> Result<&T, (T, &T)>
+where T: AbstractOrd<T>, A: Allocator
 {
+    insert_by(list, elem, T::cmp)
+}
+
+// Insert `elem`, ordering it using `cmp` rather than AbstractOrd. The list must
+// always be ordered by the same comparator.
+pub(super) fn insert_by<T, F, const H: usize, A>(list: &SkipList<T, H, A>, elem: T, cmp: F)
+    -> Result<&T, (T, &T)>
+where F: Fn(&T, &T) -> Ordering, A: Allocator
+{
+    insert_from(list, elem, cmp, None)
+}
+
+// Insert `elem`, beginning the search from `finger` if one is given, and
+// leaving it pointing at the new node in the lanes the node was linked into.
+pub(super) fn insert_with_finger<'a, T, const H: usize, A>(
+    list: &'a SkipList<T, H, A>,
+    elem: T,
+    finger: &mut Finger<'a, T, H>,
+) -> Result<&'a T, (T, &'a T)>
+where T: AbstractOrd<T>, A: Allocator
+{
+    insert_from(list, elem, T::cmp, Some(finger))
+}
+
+fn insert_from<'a, T, F, const H: usize, A>(
+    list: &'a SkipList<T, H, A>,
+    elem: T,
+    cmp: F,
+    mut finger: Option<&mut Finger<'a, T, H>>,
+) -> Result<&'a T, (T, &'a T)>
+where F: Fn(&T, &T) -> Ordering, A: Allocator
+{
+    let lanes: &'a [AtomicPtr<Node<T>>] = &list.lanes[..];
+
     // This wonky memory set up is necessary to handle retry iteration: we do
     // not know we need to retry the insertion until after we have already
     // allocated a node for this element. We are faced with a dilemma because
@@ -28,11 +70,24 @@ where T: AbstractOrd<T>
     // For this reason, we access the element during search through the
     // "elem_ptr" variable, which could point either to the element in the
     // stack or the element in the heap. We manage dropping/forgetting the elem
-    // correctly using a ManuallyDrop wrapper. The `new_node` pointer is used
-    // to track if the node has been allocated (it has if it is non-null).
-    let mut elem: ManuallyDrop<T> = ManuallyDrop::new(elem);
-    let mut elem_ptr: NonNull<T> = NonNull::from(&*elem);
-    let mut new_node: Ptr<Node<T>> = None;
+    // correctly using the Unlinked guard, whose `node` pointer is used to
+    // track if the node has been allocated (it has if it is non-null).
+    let mut unlinked = Unlinked { elem: ManuallyDrop::new(elem), node: None, alloc: &list.alloc };
+    let mut elem_ptr: NonNull<T> = NonNull::from(&*unlinked.elem);
+
+    // The immediate predecessor and successor of this element in each lane of
+    // the skiplist, as found by search.
+    let mut spots: [Spot<T>; H] = [(ptr::null(), ptr::null_mut()); H];
+
+    // A search beginning from a finger only finds the spots in the lanes
+    // below where it began. If the new node is taller than that, we search
+    // again from the head of the list.
+    let mut from_head = false;
+
+    // How many times the insert has lost the race to link its node into the
+    // lowest lane; see backoff.
+    let mut contended = 0;
+    let mut retries = 0;
 
     // The 'retry loop handles retrying an insert when it fails completely
     // (that is, when there  is contention inserting this node into the lowest
@@ -40,81 +95,33 @@ where T: AbstractOrd<T>
     // single `continue 'retry;`; except for that, the 'retry loop should be
     // exited on the first iteration.
     'retry: loop {
-        let mut lanes = lanes;
-        let mut height = lanes.len();
-
-        // The immediate predecessor and successor of this element in each
-        // lane of the skiplist. The predecessor pointer is a pointer to the
-        // actual AtomicPtr in that lane of that node, which will be set to
-        // point to this element. The successor pointer is just the address
-        // of the successor node, which this node's pointer will be set to,
-        // and which will be used in a compare and swap operation on the
-        // predecessor pointer.
-        let mut spots : [(*const AtomicPtr<Node<T>>, *mut Node<T>); MAX_HEIGHT];
-                spots = [(ptr::null(), ptr::null_mut()); MAX_HEIGHT];
-
-        // This is very similar to the search in get, but we track the
-        // predecessors and successors in each lane usin the `spots` variable.
-        // We iterate across the list, visting different nodes, and down each
-        // node's list of lanes, until we find the point in the lowest lane at
-        // which we are to insert our new node.
-        'across: while height > 0 {
-            'down: for atomic_ptr in lanes {
-                let ptr: Ptr<Node<T>> = NonNull::new(atomic_ptr.load(Acquire));
-
-                match ptr {
-                    // If the pointer is null, we are at the end of this lane
-                    // and we should move downward.
-                    None        => {
-                        height -= 1;
-                        spots[height] = (atomic_ptr, ptr::null_mut());
-                        continue 'down;
-                    }
-
-                    // If not, we will do a comparison between the element
-                    // to be inserted and the element at this node.
-                    Some(ptr)   => unsafe {
-                        let node: &'a Node<T> = &*ptr.as_ptr();
-                        let elem_ref: &T = elem_ptr.as_ref();
-
-                        match elem_ref.cmp(&node.inner.elem) {
-                            // If they are equal, this element has already
-                            // been inserted into the list, and we need to
-                            // return the element we attempted to insert. The
-                            // logic for this depends on whether or not we've
-                            // already allocated a node (in a previous
-                            // iteration of the 'retry loop). If we have, we
-                            // must deallocate that node to avoid leaking it.
-                            Equal   => match &mut new_node {
-                                Some(new_node)  => return Some((new_node.as_mut().dealloc(), &node.inner.elem)),
-                                None            => return Some((ManuallyDrop::take(&mut elem), &node.inner.elem)),
-                            }
-
-                            // If the element to be inserted is less than the
-                            // element in this node, we want to move down the
-                            // lanes.
-                            Less    => {
-                                height -= 1;
-                                spots[height] = (atomic_ptr, ptr.as_ptr());
-                                continue 'down;
-                            }
-
-                            // If the element to be inserted is greater than
-                            // the element in this node, we want to move across
-                            // the list, iterating through the lanes in that
-                            // node.
-                            Greater => {
-                                lanes = &node.lanes()[(node.height() - height)..];
-                                continue 'across;
-                            }
-                        }
-                    }
-                }
+        // If the element is already present, we need to return the element
+        // we attempted to insert, along with the element already present.
+        // The logic for this depends on whether or not we've already
+        // allocated a node (in a previous iteration of the 'retry loop). If
+        // we have, we must deallocate that node to avoid leaking it.
+        let key = Comparing { elem: unsafe { elem_ptr.as_ref() }, cmp: &cmp };
+        let (found, levels) = match finger.as_deref_mut() {
+            Some(finger) => {
+                let start = match from_head {
+                    false   => finger.start(list, &key),
+                    true    => Start { node: None, lanes },
+                };
+                search_from(lanes, start, &key, &mut spots, &mut finger.preds)
+            }
+            None        => {
+                let height = unlinked.node.map_or(0, |node| unsafe { node.as_ref().height() });
+                let lanes = list.lanes_for(height);
+                (search(lanes, &key, &mut spots), lanes.len())
             }
+        };
+        if let Some(node) = found {
+            list.stats.retried(retries);
+            return Err((unlinked.into_elem(), &node.inner.elem));
         }
 
         // Allocate the new node if it hasn't already been allocated.
-        let new_node: NonNull<Node<T>> = match new_node {
+        let new_node: NonNull<Node<T>> = match unlinked.node {
             // If the node is not null, its already been allocated and there is
             // no work to be done.
             Some(new_node)  => new_node,
@@ -123,14 +130,20 @@ where T: AbstractOrd<T>
             // reset the elem_ptr to point into the heap instead of to the old
             // location on the stack.
             None        => {
-                let elem = unsafe { ManuallyDrop::take(&mut elem) };
-                let node = Node::alloc(elem, max_height);
+                let height = list.heights.next_with(H, list.branching as u32);
+                let elem = unsafe { ManuallyDrop::take(&mut unlinked.elem) };
+                let node = Node::alloc(elem, height, &list.current_height, &list.alloc);
                 elem_ptr = unsafe { NonNull::from(&node.as_ref().inner.elem) };
-                new_node = Some(node);
-                new_node.unwrap()
+                unlinked.node = Some(node);
+                node
             }
         };
 
+        if unsafe { new_node.as_ref().height() } > levels {
+            from_head = true;
+            continue 'retry;
+        }
+
         // The insert loop iterates upward from the lowest lane of this node
         // to its highest, attempting to insert it at each point, performing
         // an atomic compare and swap to identify conflicts with concurrent
@@ -141,20 +154,62 @@ where T: AbstractOrd<T>
         let new_node_addr = new_node.as_ptr();
         let new_node_lanes = unsafe { new_node.as_ref().lanes() };
         let mut inserted = false;
+        let mut linked_lanes = 0;
 
         'insert: for (new, &(pred, succ)) in new_node_lanes.iter().rev().zip(&spots) {
             let pred: &'a AtomicPtr<Node<T>> = unsafe { &*pred };
 
-            new.store(succ, Release);
+            // Until the node is in the lowest lane no other thread can see
+            // it, but after that it may be concurrently removed, which marks
+            // each of its lanes. If this lane has been marked, we must not
+            // overwrite the mark, and we stop inserting the node.
+            if !inserted {
+                new.store(succ, Release);
+            } else if new.compare_exchange(ptr::null_mut(), succ, AcqRel, Relaxed).is_err() {
+                break 'insert;
+            }
 
-            match succ == pred.compare_and_swap(succ, new_node_addr, AcqRel) {
+            // On success the exchange must release the node's contents and
+            // its link to `succ` to any thread which loads it from `pred`, and
+            // acquire whatever was published with `succ`. On failure nothing
+            // is read through the returned pointer: it is only compared
+            // against `succ`, and the retry searches with its own loads, so
+            // the failure ordering is Relaxed.
+            //
+            // A weak exchange may fail spuriously, returning the pointer we
+            // expected. Nothing has changed in that case, so we retry the
+            // exchange directly rather than searching the list again.
+            let linked = loop {
+                match pred.compare_exchange_weak(succ, new_node_addr, AcqRel, Relaxed) {
+                    Ok(_)                           => break true,
+                    Err(current) if current == succ => continue,
+                    Err(_)                          => {
+                        list.stats.cas_failed();
+                        break false
+                    }
+                }
+            };
+
+            if linked { linked_lanes += 1; }
+
+            match linked {
                 // We successfully inserted the node into at least one lane,
-                // we note that for future iterations.
-                true                => inserted = true,
+                // we note that for future iterations. The first successful
+                // lane is the lowest, which is when the element becomes a
+                // member of the list and is counted.
+                true if !inserted   => {
+                    inserted = true;
+                    list.len.fetch_add(1, Relaxed);
+                }
+                true                => { }
 
                 // Because the node has not been inserted yet, we need to retry
                 // the entire insertion on this failure.
-                false if !inserted  => continue 'retry,
+                false if !inserted  => {
+                    backoff(&mut contended);
+                    retries += 1;
+                    continue 'retry;
+                }
 
                 // Because the node has been inserted into at least one lane
                 // of the list, we just finish the insertion here.
@@ -162,6 +217,238 @@ where T: AbstractOrd<T>
             }
         }
 
-        return None;
+        if let Some(finger) = finger {
+            for pred in &mut finger.preds[..linked_lanes] {
+                *pred = Some(new_node);
+            }
+        }
+
+        mem::forget(unlinked);
+        list.stats.retried(retries);
+        return Ok(unsafe { &*elem_ptr.as_ptr() });
     }
 }
+
+// After losing a race to link a node, wait before searching again, for twice
+// as long each time up to a limit, so that threads contending for the same
+// spot spread out rather than repeatedly invalidating each other's searches.
+// An insert returns as soon as its node is linked, so the count starts over
+// with each insert.
+fn backoff(contended: &mut u32) {
+    for _ in 0..(1 << *contended) {
+        spin_loop();
+    }
+    if *contended < 6 { *contended += 1; }
+}
+
+// The element being inserted, until its node has been linked into the list.
+// If a comparison panics during the search, the guard drops the element,
+// deallocating its node if one has already been allocated.
+struct Unlinked<'a, T, A: Allocator> {
+    elem: ManuallyDrop<T>,
+    node: Ptr<Node<T>>,
+    alloc: &'a A,
+}
+
+impl<'a, T, A: Allocator> Unlinked<'a, T, A> {
+    fn into_elem(mut self) -> T {
+        let elem = match &mut self.node {
+            Some(node)  => unsafe { node.as_mut().dealloc(self.alloc) },
+            None        => unsafe { ManuallyDrop::take(&mut self.elem) },
+        };
+        mem::forget(self);
+        elem
+    }
+}
+
+impl<'a, T, A: Allocator> Drop for Unlinked<'a, T, A> {
+    fn drop(&mut self) {
+        match &mut self.node {
+            Some(node)  => unsafe { drop(node.as_mut().dealloc(self.alloc)) },
+            None        => unsafe { ManuallyDrop::drop(&mut self.elem) },
+        }
+    }
+}
+
+// Search for the position of `elem` in the list, recording its predecessor and
+// successor in each lane in `spots`. If an equal element is present, the node
+// containing it is returned instead.
+//
+// We iterate across the list, visting different nodes, and down each node's
+// list of lanes, until we find the point in the lowest lane at which the
+// element belongs. This is very similar to the search in get, except that this
+// search also unlinks any removed nodes it encounters, so that the recorded
+// predecessors and successors are never marked.
+pub(super) fn search<'a, T, U>(lanes: &'a [AtomicPtr<Node<T>>], elem: &U, spots: &mut [Spot<T>])
+    -> Option<&'a Node<T>>
+where U: AbstractOrd<T> + ?Sized
+{
+    search_from(lanes, Start { node: None, lanes }, elem, spots, &mut []).0
+}
+
+// Where a search begins: the lanes of `node`, or of the head of the list if it
+// is None, from the height at which to begin downward.
+pub(super) struct Start<'a, T> {
+    pub(super) node: Option<&'a Node<T>>,
+    pub(super) lanes: &'a [AtomicPtr<Node<T>>],
+}
+
+// The same search, but beginning from `start` rather than the head of the
+// list, and also recording the predecessor node in each lane in `preds`, if it
+// is long enough. The start must be a node which precedes `elem`, so only the
+// lanes at or below the height it begins from are recorded; this returns how
+// many there are. If the start node is removed during the search, the search
+// begins again from the head, which has every lane.
+pub(super) fn search_from<'a, T, U>(
+    head: &'a [AtomicPtr<Node<T>>],
+    start: Start<'a, T>,
+    elem: &U,
+    spots: &mut [Spot<T>],
+    preds: &mut [Ptr<Node<T>>],
+) -> (Option<&'a Node<T>>, usize)
+where U: AbstractOrd<T> + ?Sized
+{
+    let mut start = start;
+
+    'retry: loop {
+        let mut lanes = start.lanes;
+        let mut pred = start.node;
+        let mut height = lanes.len();
+        let levels = height;
+        start = Start { node: None, lanes: head };
+
+        'across: while height > 0 {
+            'down: for atomic_ptr in lanes {
+                // These loads must be Acquire, like those in get: the node may
+                // have just been linked by another thread, and we are about to
+                // read its element and lanes, which that thread wrote before
+                // the Release half of its compare and swap published it.
+                let mut ptr: *mut Node<T> = atomic_ptr.load(Acquire);
+
+                // If this pointer is marked, the node it belongs to is being
+                // removed and cannot be a predecessor; start again from the
+                // head of the list.
+                if marked(ptr) { continue 'retry; }
+
+                // If the node this pointer points to has been marked in this
+                // lane, it is being removed; we unlink it by pointing past it
+                // to its successor. If that fails, our predecessor has changed
+                // and we start again from the head of the list.
+                while let Some(node) = unsafe { ptr.as_ref() } {
+                    let next = node.lanes()[node.height() - height].load(Acquire);
+                    if !marked(next) { break; }
+                    match atomic_ptr.compare_exchange(ptr, unmark(next), AcqRel, Acquire) {
+                        Ok(_)   => ptr = unmark(next),
+                        Err(_)  => continue 'retry,
+                    }
+                }
+
+                match NonNull::new(ptr) {
+                    // If the pointer is null, we are at the end of this lane
+                    // and we should move downward.
+                    None        => {
+                        height -= 1;
+                        spots[height] = (atomic_ptr, ptr::null_mut());
+                        if let Some(p) = preds.get_mut(height) { *p = pred.map(NonNull::from) }
+                        continue 'down;
+                    }
+
+                    // If not, we will do a comparison between the element
+                    // and the element at this node.
+                    Some(ptr)   => {
+                        let node: &'a Node<T> = unsafe { &*ptr.as_ptr() };
+
+                        match elem.cmp(&node.inner.elem) {
+                            // If they are equal, the element is present.
+                            // Its predecessor here precedes it in every lower
+                            // lane as well.
+                            Equal   => {
+                                for p in preds.iter_mut().take(height) {
+                                    *p = pred.map(NonNull::from);
+                                }
+                                return (Some(node), levels);
+                            }
+
+                            // If the element is less than the element in
+                            // this node, we want to move down the lanes.
+                            Less    => {
+                                height -= 1;
+                                spots[height] = (atomic_ptr, ptr.as_ptr());
+                                if let Some(p) = preds.get_mut(height) { *p = pred.map(NonNull::from) }
+                                continue 'down;
+                            }
+
+                            // If the element is greater than the element in
+                            // this node, we want to move across the list,
+                            // iterating through the lanes in that node.
+                            Greater => {
+                                lanes = &node.lanes()[(node.height() - height)..];
+                                pred = Some(node);
+                                continue 'across;
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        return (None, levels);
+    }
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn test_panicking_cmp() {
+    use std::panic::{self, AssertUnwindSafe};
+    use std::sync::atomic::{AtomicUsize, Ordering::Relaxed};
+
+    static DROPS: AtomicUsize = AtomicUsize::new(0);
+
+    // Comparing against a negative value panics.
+    #[derive(PartialEq, Eq)] struct Panicky(i32);
+    impl Drop for Panicky { fn drop(&mut self) {
+        DROPS.fetch_add(1, Relaxed);
+    } }
+    impl PartialOrd for Panicky { fn partial_cmp(&self, rhs: &Panicky) -> Option<Ordering> {
+        Some(Ord::cmp(self, rhs))
+    } }
+    impl Ord for Panicky { fn cmp(&self, rhs: &Panicky) -> Ordering {
+        assert!(self.0 >= 0 && rhs.0 >= 0);
+        Ord::cmp(&self.0, &rhs.0)
+    } }
+
+    let list: SkipList<Panicky> = SkipList::new();
+    list.insert(Panicky(1));
+    list.insert(Panicky(2));
+
+    let result = panic::catch_unwind(AssertUnwindSafe(|| list.insert(Panicky(-1))));
+    assert!(result.is_err());
+    assert_eq!(DROPS.load(Relaxed), 1);
+    assert_eq!(list.len(), 2);
+    assert!(list.elems().map(|x| x.0).eq([1, 2]));
+
+    drop(list);
+    assert_eq!(DROPS.load(Relaxed), 3);
+}
+
+#[cfg(loom)]
+#[test]
+fn loom_insert() {
+    use loom::sync::Arc;
+    use loom::thread;
+
+    // A short list keeps the number of interleavings loom explores tractable.
+    loom::model(|| {
+        let list: Arc<SkipList<i32, 2>> = Arc::new(SkipList::with_seed(0));
+        let thread = {
+            let list = list.clone();
+            thread::spawn(move || assert!(list.insert(2).is_none()))
+        };
+        assert!(list.insert(1).is_none());
+        thread.join().unwrap();
+
+        assert_eq!(list.get(&1), Some(&1));
+        assert_eq!(list.get(&2), Some(&2));
+        assert_eq!(list.len(), 2);
+    });
+}
@@ -1,8 +1,17 @@
-use std::marker::PhantomData;
-use std::mem;
-use std::ptr::{self, NonNull};
+use core::cmp::{self, Ordering::*};
+use alloc::collections::VecDeque;
+use core::iter::{self, FusedIterator};
+use core::marker::PhantomData;
+use core::ops::Bound::*;
+use core::ops::RangeBounds;
+use core::ptr::{self, NonNull};
+use core::sync::atomic::Ordering::Relaxed;
 
-use super::{Ptr, Node};
+
+use crate::AbstractOrd;
+use crate::sync::{AtomicMut, AtomicPtr};
+use super::build::Tails;
+use super::{get, Allocator, Global, Ptr, Node, SkipList, MAX_HEIGHT};
 
 pub(super) struct Nodes<'a, T> {
     ptr: Ptr<Node<T>>,
@@ -13,49 +22,138 @@ impl<'a, T> Nodes<'a, T> {
     pub(super) fn new(ptr: Ptr<Node<T>>) -> Nodes<'a, T> {
         Nodes { ptr, _marker: PhantomData }
     }
+
+    // Skips any removed nodes, returning the next node without advancing
+    // past it.
+    fn peek(&mut self) -> Option<&'a Node<T>> {
+        loop {
+            let node: &'a Node<T> = unsafe { &*self.ptr?.as_ptr() };
+            if !node.is_removed() {
+                return Some(node);
+            }
+            self.ptr = node.next();
+        }
+    }
 }
 
+// Clone is implemented by hand for the borrowing iterators, because deriving
+// it would require T: Clone.
+impl<'a, T> Clone for Nodes<'a, T> {
+    fn clone(&self) -> Nodes<'a, T> {
+        Nodes { ptr: self.ptr, _marker: PhantomData }
+    }
+}
+
+// Nodes skips over nodes which have been removed, but NodesMut does not, so
+// that it can be used to deallocate every node in the list.
+//
+// Node::next loads each link with Acquire, pairing with the Release half of
+// the compare and swap which linked the node, so the iterator never reads a
+// node before its element has been published. Nodes are only deallocated with
+// exclusive access to the list, so none is freed while a Nodes borrows it.
+// The iteration is not a snapshot, though: it sees each element inserted or
+// removed concurrently ahead of it in the list, or not, depending on the race.
 impl<'a, T> Iterator for Nodes<'a, T> {
     type Item = &'a Node<T>;
     fn next(&mut self) -> Option<&'a Node<T>> {
-        unsafe {
+        loop {
             let ptr: NonNull<Node<T>> = self.ptr.take()?;
-            {
-                let node: &Node<T> = ptr.as_ref();
-                self.ptr = node.next();
+            let node: &'a Node<T> = unsafe { &*ptr.as_ptr() };
+            self.ptr = node.next();
+            if !node.is_removed() {
+                return Some(node);
             }
-            mem::transmute(ptr)
+        }
+    }
+}
+
+// NodesMut iterates over nodes to which it has exclusive access, so it can
+// also iterate backward. Nodes have no back pointers, which would cost every
+// node another word; instead, the first time the cursor moves backward, it
+// walks the remaining nodes and collects them, and from then on it takes
+// nodes from either end of that collection. `back` is None until then.
+//
+// The cursor is separate from NodesMut so that IntoElems, which has no
+// lifetime, can share it.
+pub(super) struct RawCursor<T> {
+    front: Ptr<Node<T>>,
+    back: Option<VecDeque<NonNull<Node<T>>>>,
+}
+
+impl<T> RawCursor<T> {
+    pub(super) fn new(front: Ptr<Node<T>>) -> RawCursor<T> {
+        RawCursor { front, back: None }
+    }
+
+    // Safety: the cursor must have exclusive access to the remaining nodes.
+    unsafe fn next(&mut self) -> Ptr<Node<T>> {
+        if let Some(nodes) = &mut self.back {
+            return nodes.pop_front();
+        }
+        let ptr = self.front?;
+        self.front = ptr.as_ref().next();
+        Some(ptr)
+    }
+
+    // Safety: the cursor must have exclusive access to the remaining nodes.
+    unsafe fn next_back(&mut self) -> Ptr<Node<T>> {
+        let front = &mut self.front;
+        let nodes = self.back.get_or_insert_with(|| {
+            iter::successors(front.take(), |node| node.as_ref().next()).collect()
+        });
+        nodes.pop_back()
+    }
+
+    fn peek(&self) -> Ptr<Node<T>> {
+        match &self.back {
+            Some(nodes) => nodes.front().copied(),
+            None        => self.front,
         }
     }
 }
 
 pub(super) struct NodesMut<'a, T> {
-    ptr: Ptr<Node<T>>,
+    cursor: RawCursor<T>,
     _marker: PhantomData<&'a mut T>,
 }
 
 impl<'a, T> NodesMut<'a, T> {
     pub(super) fn new(ptr: Ptr<Node<T>>) -> NodesMut<'a, T> {
-        NodesMut { ptr, _marker: PhantomData }
+        NodesMut { cursor: RawCursor::new(ptr), _marker: PhantomData }
     }
 }
 
 impl<'a, T> Iterator for NodesMut<'a, T> {
     type Item = &'a mut Node<T>;
     fn next(&mut self) -> Option<&'a mut Node<T>> {
-        unsafe {
-            let ptr: NonNull<Node<T>> = self.ptr.take()?;
-            {
-                let node: &Node<T> = ptr.as_ref();
-                self.ptr = node.next();
-            }
-            mem::transmute(ptr)
-        }
+        unsafe { self.cursor.next().map(|ptr| &mut *ptr.as_ptr()) }
+    }
+}
+
+impl<'a, T> DoubleEndedIterator for NodesMut<'a, T> {
+    fn next_back(&mut self) -> Option<&'a mut Node<T>> {
+        unsafe { self.cursor.next_back().map(|ptr| &mut *ptr.as_ptr()) }
     }
 }
 
+// Elems also keeps the head lanes of its list. Every element remaining is
+// before the last element of the list, so Elems can find its last element by
+// descending the list rather than walking it.
 pub struct Elems<'a, T> {
-    pub(super) nodes: Nodes<'a, T>
+    pub(super) nodes: Nodes<'a, T>,
+    pub(super) lanes: &'a [AtomicPtr<Node<T>>],
+}
+
+impl<'a, T> Clone for Elems<'a, T> {
+    fn clone(&self) -> Elems<'a, T> {
+        Elems { nodes: self.nodes.clone(), lanes: self.lanes }
+    }
+}
+
+impl<'a, T> Elems<'a, T> {
+    pub fn peek(&mut self) -> Option<&'a T> {
+        self.nodes.peek().map(|node| &node.inner.elem)
+    }
 }
 
 impl<'a, T> Iterator for Elems<'a, T> {
@@ -64,35 +162,443 @@ impl<'a, T> Iterator for Elems<'a, T> {
     fn next(&mut self) -> Option<Self::Item> {
         self.nodes.next().map(|node| &node.inner.elem)
     }
+
+    // If the last node has been removed, we walk to the last element instead.
+    fn last(mut self) -> Option<Self::Item> {
+        self.nodes.peek()?;
+        match get::last(self.lanes) {
+            Some(last) if !unsafe { last.as_ref() }.is_removed() => {
+                Some(unsafe { &(*last.as_ptr()).inner.elem })
+            }
+            _ => self.fold(None, |_, elem| Some(elem)),
+        }
+    }
+}
+
+impl<'a, T> FusedIterator for Elems<'a, T> { }
+
+// Towers yields each element with the height of its node, for inspecting the
+// structure of the list.
+pub struct Towers<'a, T> {
+    pub(super) nodes: Nodes<'a, T>
+}
+
+impl<'a, T> Iterator for Towers<'a, T> {
+    type Item = (&'a T, u8);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.nodes.next().map(|node| (&node.inner.elem, node.inner.height))
+    }
 }
 
+impl<'a, T> FusedIterator for Towers<'a, T> { }
+
+// The exclusive iterators carry the number of remaining elements: no other
+// thread can insert into or remove from the list while they exist, so the
+// length of the list when they were created is exact.
 pub struct ElemsMut<'a, T> {
-    pub(super) nodes: NodesMut<'a, T>
+    pub(super) nodes: NodesMut<'a, T>,
+    pub(super) len: usize,
+}
+
+impl<'a, T> ElemsMut<'a, T> {
+    pub fn peek_mut(&mut self) -> Option<&mut T> {
+        loop {
+            let node: &mut Node<T> = unsafe { &mut *self.nodes.cursor.peek()?.as_ptr() };
+            if !node.is_removed() {
+                return Some(&mut node.inner.elem);
+            }
+            unsafe { self.nodes.cursor.next(); }
+        }
+    }
 }
 
 impl<'a, T> Iterator for ElemsMut<'a, T> {
     type Item = &'a mut T;
 
     fn next(&mut self) -> Option<Self::Item> {
-        self.nodes.next().map(|node| &mut node.inner.elem)
+        let node = self.nodes.find(|node| !node.is_removed())?;
+        self.len -= 1;
+        Some(&mut node.inner.elem)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.len, Some(self.len))
     }
 }
 
-pub struct IntoElems<T> {
-    pub(super) ptr: Ptr<Node<T>>,
+impl<'a, T> DoubleEndedIterator for ElemsMut<'a, T> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        let node = self.nodes.rfind(|node| !node.is_removed())?;
+        self.len -= 1;
+        Some(&mut node.inner.elem)
+    }
 }
 
-impl<T> Iterator for IntoElems<T> {
+impl<'a, T> ExactSizeIterator for ElemsMut<'a, T> { }
+
+impl<'a, T> FusedIterator for ElemsMut<'a, T> { }
+
+pub struct IntoElems<T, A: Allocator = Global> {
+    pub(super) cursor: RawCursor<T>,
+    pub(super) len: usize,
+    pub(super) alloc: A,
+}
+
+impl<T, A: Allocator> Iterator for IntoElems<T, A> {
     type Item = T;
 
     fn next(&mut self) -> Option<Self::Item> {
-        unsafe {
-            let mut ptr = self.ptr.take()?;
-            let node: &mut Node<T> = ptr.as_mut();
-            self.ptr = node.next();
-            let elem = ptr::read(&mut node.inner.elem as *mut T);
-            node.dealloc();
-            Some(elem)
+        let node: &mut Node<T> = unsafe { &mut *self.cursor.next()?.as_ptr() };
+        self.len -= 1;
+        unsafe { Some(node.dealloc(&self.alloc)) }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.len, Some(self.len))
+    }
+}
+
+impl<T, A: Allocator> DoubleEndedIterator for IntoElems<T, A> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        let node: &mut Node<T> = unsafe { &mut *self.cursor.next_back()?.as_ptr() };
+        self.len -= 1;
+        unsafe { Some(node.dealloc(&self.alloc)) }
+    }
+}
+
+impl<T, A: Allocator> ExactSizeIterator for IntoElems<T, A> { }
+
+// The list was forgotten when it was converted into this iterator, so any
+// nodes which were not yielded must be freed here.
+impl<T, A: Allocator> Drop for IntoElems<T, A> {
+    fn drop(&mut self) {
+        while let Some(ptr) = unsafe { self.cursor.next() } {
+            unsafe { drop((*ptr.as_ptr()).dealloc(&self.alloc)) }
+        }
+    }
+}
+
+impl<T, A: Allocator> FusedIterator for IntoElems<T, A> { }
+
+// Drain frees the nodes it yields with the list's allocator, which it borrows
+// for as long as it holds the list's former nodes.
+pub struct Drain<'a, T, A: Allocator = Global> {
+    pub(super) inner: IntoElems<T, &'a A>,
+}
+
+impl<'a, T, A: Allocator> Iterator for Drain<'a, T, A> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<'a, T, A: Allocator> DoubleEndedIterator for Drain<'a, T, A> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.inner.next_back()
+    }
+}
+
+impl<'a, T, A: Allocator> ExactSizeIterator for Drain<'a, T, A> { }
+
+impl<'a, T, A: Allocator> FusedIterator for Drain<'a, T, A> { }
+
+// Because extracting takes exclusive access to the list, it relinks the list
+// as it goes, like retain. The list is detached from its nodes when the
+// extraction begins, and walking the lowest lane, each extracted node is
+// deallocated and each other node is linked back onto the end of the list,
+// with its own lanes terminated. When it is dropped, the nodes it has not
+// reached are linked on unexamined. Nodes removed while the list was shared
+// are freed before the extraction begins, so every node it reaches is live.
+//
+// The list is consistent after every step, holding only the nodes relinked so
+// far, so if the extraction is forgotten, the nodes it has not reached are
+// leaked rather than left reachable after some of them have been freed. If the
+// predicate panics, the node it was examining has not been taken from the
+// cursor yet, so it is relinked with the rest as the extraction is dropped.
+//
+// The predicate is passed to each call to next rather than kept, so that maps
+// and sets can adapt their own predicates.
+pub(crate) struct RawExtract<'a, T, const H: usize, A: Allocator> {
+    list: &'a mut SkipList<T, H, A>,
+    cursor: RawCursor<T>,
+    tails: Tails<T, H>,
+    len: usize,
+    height: u8,
+}
+
+impl<'a, T, const H: usize, A: Allocator> RawExtract<'a, T, H, A> {
+    pub(crate) fn new(list: &'a mut SkipList<T, H, A>) -> RawExtract<'a, T, H, A> {
+        list.reclaim();
+        let cursor = RawCursor::new(list.head());
+        list.reset();
+        let tails = Tails::new(&list.lanes);
+        RawExtract { list, cursor, tails, len: 0, height: SkipList::<T, H, A>::INITIAL_HEIGHT }
+    }
+
+    pub(crate) fn next<F: FnMut(&mut T) -> bool>(&mut self, mut f: F) -> Option<T> {
+        loop {
+            // The node stays in the cursor until the predicate has returned, so
+            // that if it panics, the node is relinked when this is dropped.
+            let node: &mut Node<T> = unsafe { &mut *self.cursor.peek()?.as_ptr() };
+            let extract = f(&mut node.inner.elem);
+            unsafe { self.cursor.next(); }
+            if extract {
+                return unsafe { Some(node.dealloc(&self.list.alloc)) };
+            }
+
+            // The cursor has already read this node's successor, so its lanes
+            // can be terminated before it is linked back onto the list.
+            for lane in node.lanes() {
+                lane.store(ptr::null_mut(), Relaxed);
+            }
+            self.len += 1;
+            self.height = cmp::max(self.height, node.inner.height);
+            self.tails.push(NonNull::from(node));
+            self.list.len.set_mut(self.len);
+            self.list.current_height.set_mut(self.height);
+        }
+    }
+}
+
+impl<'a, T, const H: usize, A: Allocator> Drop for RawExtract<'a, T, H, A> {
+    fn drop(&mut self) {
+        while self.next(|_| false).is_some() { }
+    }
+}
+
+// Iterating extracts each element for which the predicate returns true. If the
+// iterator is dropped before it is exhausted, the elements it has not reached
+// remain in the list, as with the extract_if methods of std's collections.
+pub struct ExtractIf<'a, T, F, const H: usize = MAX_HEIGHT, A: Allocator = Global> {
+    pub(super) raw: RawExtract<'a, T, H, A>,
+    pub(super) pred: F,
+}
+
+impl<'a, T, F, const H: usize, A> Iterator for ExtractIf<'a, T, F, H, A>
+where
+    F: FnMut(&T) -> bool,
+    A: Allocator,
+{
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        let pred = &mut self.pred;
+        self.raw.next(|elem| pred(elem))
+    }
+}
+
+impl<'a, T, F, const H: usize, A> FusedIterator for ExtractIf<'a, T, F, H, A>
+where
+    F: FnMut(&T) -> bool,
+    A: Allocator,
+{ }
+
+// The upper bound is checked against every element, rather than computing the
+// node at which to stop up front, because a concurrent insert may link a node
+// past the bound but before that end node.
+pub struct Range<'a, T, U: ?Sized, R> {
+    pub(super) nodes: Nodes<'a, T>,
+    pub(super) range: R,
+    pub(super) _marker: PhantomData<fn(&U)>,
+}
+
+impl<'a, T, U, R> Iterator for Range<'a, T, U, R>
+where
+    U: AbstractOrd<T> + ?Sized,
+    R: RangeBounds<U>,
+{
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let elem = &self.nodes.next()?.inner.elem;
+        let in_range = match self.range.end_bound() {
+            Included(end)   => end.cmp(elem) != Less,
+            Excluded(end)   => end.cmp(elem) == Greater,
+            Unbounded       => true,
+        };
+
+        match in_range {
+            true    => Some(elem),
+            false   => {
+                self.nodes.ptr = None;
+                None
+            }
         }
     }
 }
+
+impl<'a, T, U, R> FusedIterator for Range<'a, T, U, R>
+where
+    U: AbstractOrd<T> + ?Sized,
+    R: RangeBounds<U>,
+{ }
+
+#[cfg(feature = "std")]
+#[test]
+fn test_into_elems_drop() {
+    use std::sync::atomic::{AtomicUsize, Ordering::Relaxed};
+    use crate::{Map, Set};
+    use super::SkipList;
+
+    static DROPS: AtomicUsize = AtomicUsize::new(0);
+
+    #[derive(Debug, Ord, PartialOrd, Eq, PartialEq)] struct DropInt(i32);
+    impl Drop for DropInt { fn drop(&mut self) {
+        DROPS.fetch_add(1, Relaxed);
+    } }
+
+    let list: SkipList<DropInt> = (0..10).map(DropInt).collect();
+    let mut elems = list.into_elems();
+    assert_eq!(elems.next(), Some(DropInt(0)));
+    assert_eq!(elems.next_back(), Some(DropInt(9)));
+    drop(elems);
+    // 10 elements, plus the 2 compared against above.
+    assert_eq!(DROPS.swap(0, Relaxed), 12);
+
+    let set: Set<DropInt> = (0..10).map(DropInt).collect();
+    set.into_iter().next();
+    assert_eq!(DROPS.swap(0, Relaxed), 10);
+
+    let map: Map<i32, DropInt> = (0..10).map(|x| (x, DropInt(x))).collect();
+    map.into_iter().next();
+    assert_eq!(DROPS.swap(0, Relaxed), 10);
+}
+
+#[test]
+fn test_peek() {
+    use super::SkipList;
+
+    let mut list: SkipList<i32> = (0..5).collect();
+    list.remove(&0);
+    let mut elems = list.elems();
+    assert_eq!(elems.peek(), Some(&1));
+    assert_eq!(elems.next(), Some(&1));
+    assert_eq!(elems.peek(), Some(&2));
+
+    let mut elems = list.elems_mut();
+    *elems.peek_mut().unwrap() *= 10;
+    assert_eq!(elems.len(), 4);
+    assert_eq!(elems.next(), Some(&mut 10));
+    assert_eq!(elems.next_back(), Some(&mut 4));
+    elems.nth(1);
+    assert_eq!(elems.peek_mut(), None);
+    assert!(list.elems().copied().eq([10, 2, 3, 4]));
+
+    // Peeking still sees the front once the iterator has moved backward.
+    let mut list: SkipList<i32> = (0..5).collect();
+    list.remove(&0);
+    let mut elems = list.elems_mut();
+    assert_eq!(elems.next_back(), Some(&mut 4));
+    assert_eq!(elems.peek_mut(), Some(&mut 1));
+    assert_eq!(elems.next(), Some(&mut 1));
+    assert_eq!(elems.next_back(), Some(&mut 3));
+    assert_eq!(elems.peek_mut(), Some(&mut 2));
+    assert_eq!(elems.next(), Some(&mut 2));
+    assert_eq!(elems.peek_mut(), None);
+}
+
+#[test]
+fn test_elems_last() {
+    use super::SkipList;
+
+    let list: SkipList<i32> = (0..1000).collect();
+    assert_eq!(list.elems().last(), Some(&999));
+    assert_eq!(list.elems_from(&500).last(), Some(&999));
+    assert_eq!(list.elems_from(&1000).last(), None);
+
+    list.remove(&999);
+    assert_eq!(list.elems().last(), Some(&998));
+    assert_eq!(SkipList::<i32>::new().elems().last(), None);
+}
+
+#[test]
+fn test_clone_iters() {
+    use crate::{Map, Set};
+    use super::SkipList;
+
+    let list: SkipList<i32> = (0..10).collect();
+    let mut elems = list.elems();
+    elems.nth(4);
+    assert!(elems.clone().take(2).eq(&[5, 6]));
+    assert!(elems.eq(&[5, 6, 7, 8, 9]));
+
+    let set: Set<i32> = (0..10).collect();
+    let iter = set.iter_from(&8);
+    assert!(iter.clone().eq(iter));
+
+    let map: Map<i32, i32> = (0..10).map(|x| (x, x * 2)).collect();
+    let keys = map.keys();
+    assert!(keys.clone().eq(keys));
+    let values = map.values();
+    assert_eq!(values.clone().count(), values.count());
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn test_extract_if() {
+    use crate::{Map, Set};
+    use super::SkipList;
+
+    let mut list: SkipList<i32> = (0..20).collect();
+    list.remove(&4);
+    assert!(list.extract_if(|x| x % 2 == 0).eq([0, 2, 6, 8, 10, 12, 14, 16, 18]));
+    assert!(list.elems().copied().eq((1..20).step_by(2)));
+    assert_eq!(list.len(), 10);
+    assert_eq!(list.get(&19), Some(&19));
+
+    // Dropping the iterator early leaves the rest of the list in place.
+    assert_eq!(list.extract_if(|x| x % 3 == 0).next(), Some(3));
+    assert!(list.elems().copied().eq([1, 5, 7, 9, 11, 13, 15, 17, 19]));
+    assert_eq!(list.len(), 9);
+
+    // A predicate which panics leaves the element it was examining in place.
+    let mut list: SkipList<i32> = (0..10).collect();
+    let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+        list.extract_if(|&x| { assert!(x != 4); x % 2 == 0 }).count()
+    }));
+    assert!(result.is_err());
+    assert!(list.elems().copied().eq([1, 3, 4, 5, 6, 7, 8, 9]));
+    assert_eq!(list.len(), 8);
+
+    let mut set: Set<i32> = (0..10).collect();
+    assert!(set.extract_if(|&x| x >= 5).eq(5..10));
+    assert!(set.iter().copied().eq(0..5));
+
+    let mut map: Map<i32, i32> = (0..10).map(|x| (x, x)).collect();
+    let extracted: Vec<_> = map.extract_if(|&k, v| { *v *= 10; k % 2 == 1 }).collect();
+    assert_eq!(extracted, [(1, 10), (3, 30), (5, 50), (7, 70), (9, 90)]);
+    assert!(map.iter().map(|(&k, &v)| (k, v)).eq((0..10).step_by(2).map(|x| (x, x * 10))));
+}
+
+#[test]
+fn test_drain() {
+    use crate::{Map, Set};
+    use super::SkipList;
+
+    let mut list: SkipList<i32> = (0..10).collect();
+    let mut drain = list.drain();
+    assert_eq!(drain.len(), 10);
+    assert_eq!(drain.next(), Some(0));
+    assert_eq!(drain.next_back(), Some(9));
+    drop(drain);
+    assert!(list.is_empty());
+    assert_eq!(list.get(&5), None);
+    list.extend(20..25);
+    assert!(list.elems().copied().eq(20..25));
+
+    let mut set: Set<i32> = (0..10).collect();
+    assert!(set.drain().eq(0..10));
+    assert!(set.is_empty());
+
+    let mut map: Map<i32, i32> = (0..10).map(|x| (x, x * 2)).collect();
+    assert!(map.drain().eq((0..10).map(|x| (x, x * 2))));
+    map.insert(1, 1);
+    assert_eq!(map.len(), 1);
+}
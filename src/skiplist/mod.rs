@@ -1,33 +1,108 @@
+mod allocator;
+mod build;
+mod by;
+mod cursor;
+mod finger;
 mod get;
+mod height;
 mod insert;
 mod iter;
+mod remove;
+mod retired;
+mod stats;
 
-use std::alloc;
-use std::cmp;
-use std::fmt;
-use std::iter::FromIterator;
-use std::mem;
-use std::ptr::{self, NonNull};
-use std::sync::atomic::{AtomicPtr, AtomicU8};
-use std::sync::atomic::Ordering::{Relaxed, Acquire};
+use alloc::alloc::{handle_alloc_error, Layout};
+use alloc::vec::Vec;
+use core::array;
+use core::cmp;
+use core::convert::TryFrom;
+use core::fmt;
+use core::iter::FromIterator;
+use core::marker::PhantomData;
+use core::mem;
+use core::ops::{self, Bound::*};
+use core::ops::RangeBounds;
+use core::ptr::{self, NonNull};
+use core::panic::{RefUnwindSafe, UnwindSafe};
+use core::slice;
+use core::sync::atomic::Ordering::{Relaxed, Acquire};
 
 use crate::AbstractOrd;
+use crate::sync::{AtomicMut, AtomicPtr, AtomicU8, AtomicUsize};
+use self::build::Tails;
+use self::height::Heights;
+use self::retired::Retired;
+use self::stats::Stats;
 
+pub use self::allocator::{Allocator, Global};
+#[cfg(feature = "std")]
+pub use self::allocator::Recycling;
+pub use self::by::SkipListBy;
+pub use self::cursor::Cursor;
+pub use self::finger::Finger;
 pub use self::iter::*;
+#[cfg(feature = "metrics")]
+pub use self::stats::SkipListStats;
 
-const MAX_HEIGHT: usize = 31;
+pub(crate) const MAX_HEIGHT: usize = 31;
+
+// Heights are stored as u8. The height of every list is also checked, when it
+// is constructed, to be at most 64.
+const _: () = assert!(MAX_HEIGHT <= u8::MAX as usize);
 type Ptr<T>     = Option<NonNull<T>>;
 type Lanes<T>   = [AtomicPtr<Node<T>>; 1];// NB: Lanes is actually a variable sized array of lanes,
                                         // containing at least one lane, but possibly as many as
-                                        // MAX_HEIGHT.
+                                        // the height of the list.
 
-pub struct SkipList<T> {
+// The height `H` is the maximum number of lanes in the list, and so the
+// maximum height of any node. Every list contains a head array of `H` lanes, so
+// a shorter height reduces the size of small lists, while a taller height keeps
+// searches in very large lists logarithmic; a height of `H` is well suited to
+// lists of up to about 2^H elements. Node heights are stored in a `u8`, but
+// they are generated from the trailing zeros of a random `u64`, so `H` must be
+// between 1 and 64.
+//
+// The length is written by every insert and remove, so it is kept on its own
+// cache line; otherwise each of those writes would evict the head lanes from
+// the cache of every thread searching the list.
+pub struct SkipList<T, const H: usize = MAX_HEIGHT, A: Allocator = Global> {
     current_height: AtomicU8,
-    lanes: [AtomicPtr<Node<T>>; MAX_HEIGHT],
+    len: Padded<AtomicUsize>,
+    heights: Heights,
+    branching: u8,
+    retired: Retired<T>,
+    stats: Stats,
+    lanes: [AtomicPtr<Node<T>>; H],
+    alloc: A,
+}
+
+#[repr(align(64))]
+struct Padded<T>(T);
+
+impl<T> ops::Deref for Padded<T> {
+    type Target = T;
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T> ops::DerefMut for Padded<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.0
+    }
 }
 
-unsafe impl<T: Send + Sync> Send for SkipList<T> { }
-unsafe impl<T: Send + Sync> Sync for SkipList<T> { }
+unsafe impl<T: Send + Sync, const H: usize, A: Allocator + Send> Send for SkipList<T, H, A> { }
+unsafe impl<T: Send + Sync, const H: usize, A: Allocator + Sync> Sync for SkipList<T, H, A> { }
+
+// A panic cannot leave the list in an invalid state for other users of it. A
+// comparison which panics while inserting unwinds before the node has been
+// linked into the lowest lane, so the list is left as it was, and the guard in
+// insert frees the node; once the node is linked, nothing else can panic. If
+// the predicate passed to retain or extract_if panics, the list is relinked as
+// the extracting iterator unwinds, including the node it was examining.
+impl<T: RefUnwindSafe, const H: usize, A: Allocator + RefUnwindSafe> RefUnwindSafe for SkipList<T, H, A> { }
+impl<T: UnwindSafe + RefUnwindSafe, const H: usize, A: Allocator + UnwindSafe> UnwindSafe for SkipList<T, H, A> { }
 
 #[repr(C)] // NB: repr(C) necessary to avoid reordering lanes field, which must be the tail
 struct Node<T> {
@@ -43,104 +118,737 @@ struct InnerNode<T> {
 
 impl<T: AbstractOrd<T>> SkipList<T> {
     pub fn new() -> SkipList<T> {
-        SkipList {
-            current_height: AtomicU8::new(8),
-            lanes: Default::default(),
+        SkipList::with_height()
+    }
+}
+
+impl<T: AbstractOrd<T>, const H: usize> SkipList<T, H> {
+    pub fn with_height() -> SkipList<T, H> {
+        SkipList::with_heights(Heights::Random, Global)
+    }
+
+    // Construct a list expecting to hold about `capacity` elements. Searches,
+    // whether for lookups, inserts or removals, begin from the height such a
+    // list would grow to, about log2(capacity), rather than working up to it
+    // as the list grows. Nodes are allocated
+    // individually, so no memory is reserved.
+    pub fn with_capacity(capacity: usize) -> SkipList<T, H> {
+        let mut list = SkipList::with_height();
+        let height = (usize::BITS - capacity.saturating_sub(1).leading_zeros()) as u8;
+        list.current_height.set_mut(height.clamp(Self::INITIAL_HEIGHT, H as u8));
+        list
+    }
+
+    // Construct a list which generates node heights from the given seed
+    // rather than at random, so that the same sequence of inserts always
+    // produces the same structure. Every thread draws from the list's one
+    // generator, in the order in which they allocate nodes, so inserts from
+    // several threads also produce the same structure whenever they are
+    // scheduled the same way, as they are when loom replays an interleaving.
+    pub fn with_seed(seed: u64) -> SkipList<T, H> {
+        SkipList::with_heights(Heights::seeded(seed), Global)
+    }
+
+    // Construct a list which generates node heights from the random words
+    // returned by `source`, which is called once for each node allocated, from
+    // whichever thread allocates it. Without the `std` feature, lists draw
+    // heights from a generator which always begins from the same state, so
+    // a source of real randomness should be supplied where one is available.
+    pub fn with_height_source(source: fn() -> u64) -> SkipList<T, H> {
+        SkipList::with_heights(Heights::Source(source), Global)
+    }
+
+    // Construct a list in which each node is in each lane above the lowest
+    // with probability 1/2^branching rather than 1/2. A higher branching uses
+    // less memory, since nodes average 1 + 1/(2^branching - 1) lanes rather
+    // than two, but lanes skip fewer nodes relative to the one below, so
+    // searches walk more nodes in each lane; a branching of 2 (p = 1/4)
+    // takes about a third less memory for the lanes. The branching must be
+    // between 1 and 8, and is kept by clones and split_off.
+    pub fn with_branching(branching: u32) -> SkipList<T, H> {
+        assert!((1..=8).contains(&branching), "the branching of a SkipList must be between 1 and 8");
+        let mut list = SkipList::with_height();
+        list.branching = branching as u8;
+        list
+    }
+
+    // Builds a list from elements which are already in strictly ascending
+    // order, linking each node onto the end of the list instead of searching
+    // for its position. Elements out of order produce a list which is not
+    // sorted; this is only checked in debug builds.
+    pub fn from_sorted_iter<I: IntoIterator<Item = T>>(iter: I) -> SkipList<T, H> {
+        let list = SkipList::with_height();
+        let heights = list.heights.clone();
+        list.link_sorted(iter.into_iter().map(|elem| (elem, heights.next(H))), false)
+    }
+
+    // Builds a list with exactly the given towers: each element is given a
+    // node of the height it is paired with, as yielded by towers. This
+    // rebuilds a list with the same structure as the one the towers were taken
+    // from, in linear time. Because the towers may come from outside the
+    // program, this panics if a height is not between 1 and H, or if the
+    // elements are not in strictly ascending order.
+    pub fn from_elems_with_heights<I: IntoIterator<Item = (T, u8)>>(iter: I) -> SkipList<T, H> {
+        let towers = iter.into_iter().map(|(elem, height)| {
+            let height = height as usize;
+            assert!(height >= 1 && height <= H, "height {} is not between 1 and {}", height, H);
+            (elem, height)
+        });
+        SkipList::with_height().link_sorted(towers, true)
+    }
+
+    fn link_sorted<I: Iterator<Item = (T, usize)>>(mut self, towers: I, check: bool) -> SkipList<T, H> {
+        let mut tails = Tails::new(&self.lanes);
+        let mut last: Option<NonNull<Node<T>>> = None;
+        let mut len = 0;
+
+        for (elem, height) in towers {
+            if let Some(last) = last.filter(|_| check || cfg!(debug_assertions)) {
+                let last = unsafe { &last.as_ref().inner.elem };
+                assert!(last.cmp(&elem) == cmp::Ordering::Less, "elements are not in ascending order");
+            }
+
+            let node = Node::alloc(elem, height, &self.current_height, &self.alloc);
+            tails.push(node);
+            last = Some(node);
+            len += 1;
+        }
+
+        tails.finish();
+        self.len.set_mut(len);
+        self
+    }
+}
+
+impl<T: AbstractOrd<T>, const H: usize, A: Allocator> SkipList<T, H, A> {
+    // Construct a list which allocates its nodes with `alloc`.
+    pub fn new_in(alloc: A) -> SkipList<T, H, A> {
+        SkipList::with_heights(Heights::Random, alloc)
+    }
+
+    pub fn insert(&self, elem: T) -> Option<(T, &T)> {
+        insert::insert(self, elem).err()
+    }
+
+    // Like insert, but also returns a reference to the element when it is
+    // inserted, so that using it does not require searching for it again.
+    pub fn insert_and_ref(&self, elem: T) -> Result<&T, (T, &T)> {
+        insert::insert(self, elem)
+    }
+
+    // Inserts a clone of `elem` unless the list already contains an equal
+    // element, which is returned instead. Unlike insert, which must be given
+    // the element to insert, this only clones it if it is missing.
+    pub fn try_insert_ref(&self, elem: &T) -> Option<&T>
+    where
+        T: Clone,
+    {
+        if let Some(existing) = self.get(elem) {
+            return Some(existing);
         }
+        // Another thread may insert an equal element after the search.
+        self.insert(elem.clone()).map(|(_, existing)| existing)
     }
 
-    pub fn insert<'a>(&'a self, elem: T) -> Option<(T, &'a T)> {
-        insert::insert(&self.lanes[..], elem, &self.current_height)
+    // Like insert and get, but beginning the search from where `finger` was
+    // left by the last search with it, and leaving it at `elem`. Threading one
+    // finger through a batch of nearly sorted operations makes each of them
+    // take time logarithmic in the distance from the last, rather than in the
+    // length of the list.
+    pub fn insert_with_finger<'a>(&'a self, elem: T, finger: &mut Finger<'a, T, H>) -> Option<(T, &'a T)> {
+        insert::insert_with_finger(self, elem, finger).err()
+    }
+
+    pub fn get_with_finger<'a, U>(&'a self, elem: &U, finger: &mut Finger<'a, T, H>) -> Option<&'a T>
+    where U: AbstractOrd<T> + ?Sized
+    {
+        let mut spots = [(ptr::null(), ptr::null_mut()); H];
+        let start = finger.start(self, elem);
+        let (node, _) = insert::search_from(&self.lanes, start, elem, &mut spots, &mut finger.preds);
+        node.map(|node| &node.inner.elem)
+    }
+
+    pub fn last(&self) -> Option<&T> {
+        self.last_before(None::<&T>, false)
+    }
+
+    // Returns the greatest element less than or equal to `elem`.
+    pub fn get_le<U: AbstractOrd<T> + ?Sized>(&self, elem: &U) -> Option<&T> {
+        self.last_before(Some(elem), true)
+    }
+
+    // Returns the least element greater than or equal to `elem`.
+    pub fn get_ge<U: AbstractOrd<T> + ?Sized>(&self, elem: &U) -> Option<&T> {
+        Nodes::new(get::seek(self.lanes(), elem, true)).next().map(|node| &node.inner.elem)
+    }
+
+    // Iterates over the elements greater than or equal to `elem`, in order.
+    pub fn elems_from<U: AbstractOrd<T> + ?Sized>(&self, elem: &U) -> Elems<'_, T> {
+        Elems { nodes: Nodes::new(get::seek(self.lanes(), elem, true)), lanes: self.lanes() }
+    }
+
+    fn last_before<U: AbstractOrd<T> + ?Sized>(&self, bound: Option<&U>, inclusive: bool) -> Option<&T> {
+        self.last_node_before(bound, inclusive).map(|node| unsafe { &(*node.as_ptr()).inner.elem })
+    }
+
+    fn last_node_before<U: AbstractOrd<T> + ?Sized>(&self, bound: Option<&U>, inclusive: bool) -> Ptr<Node<T>> {
+        // The node found may have been removed, in which case we look for the
+        // last node before it, until we find one which has not been removed.
+        let mut last = get::last_before(self.lanes(), bound, inclusive)?;
+        while unsafe { last.as_ref() }.is_removed() {
+            last = get::last_before(self.lanes(), Some(unsafe { &last.as_ref().inner.elem }), false)?;
+        }
+        Some(last)
+    }
+
+    pub fn get_or_insert(&self, elem: T) -> &T {
+        match insert::insert(self, elem) {
+            Ok(elem)            => elem,
+            Err((_, elem))      => elem,
+        }
+    }
+
+    // Like get_or_insert, but also reports whether `elem` was inserted. If an
+    // equal element was already present, `elem` is dropped.
+    pub fn insert_ref(&self, elem: T) -> (&T, bool) {
+        match insert::insert(self, elem) {
+            Ok(elem)            => (elem, true),
+            Err((_, elem))      => (elem, false),
+        }
+    }
+
+    // Checks the structure of the list, panicking if it is inconsistent: each
+    // lane must be in strictly ascending order, each node in a lane must also
+    // be in every lane below it, no lane may be above the current height, and
+    // the length must match the lowest lane. Nodes which have been removed but
+    // not yet unlinked are ignored. This walks every lane, and is only
+    // reliable while no other thread is modifying the list; it is meant for
+    // tests and for debugging.
+    pub fn check_invariants(&self) {
+        let height = self.current_height.load(Relaxed) as usize;
+        assert!(self.lanes[..(H - height)].iter().all(|lane| lane.load(Acquire).is_null()),
+                "lanes above the current height are not empty");
+
+        let mut below: Vec<*const Node<T>> = Vec::new();
+        for level in 1..=height {
+            let mut lane: Vec<*const Node<T>> = Vec::new();
+            let mut ptr = unmark(self.lanes[H - level].load(Acquire));
+            while let Some(node) = unsafe { ptr.as_ref() } {
+                assert!(node.height() >= level, "a node is linked into a lane above its height");
+                if !node.is_removed() {
+                    if let Some(&last) = lane.last() {
+                        let last: &Node<T> = unsafe { &*last };
+                        assert!(last.inner.elem.cmp(&node.inner.elem) == cmp::Ordering::Less,
+                                "lane {} is not in ascending order", level);
+                    }
+                    lane.push(node as *const Node<T>);
+                }
+                ptr = unmark(node.lanes()[node.height() - level].load(Acquire));
+            }
+
+            if level == 1 {
+                assert_eq!(lane.len(), self.len(), "the length does not match the lowest lane");
+            } else {
+                let mut nodes = below.iter();
+                assert!(lane.iter().all(|node| nodes.any(|below| below == node)),
+                        "lane {} is not a subsequence of the lane below it", level);
+            }
+            below = lane;
+        }
+    }
+
+    // Inserts `elem`, or if the list already contains an equal element,
+    // replaces that element with `elem` and returns it. Replacing an element
+    // in place while other threads may be reading it would be a data race, so
+    // this requires exclusive access to the list.
+    pub fn replace(&mut self, elem: T) -> Option<T> {
+        match self.get_mut(&elem) {
+            Some(old)   => Some(mem::replace(old, elem)),
+            None        => {
+                self.insert(elem);
+                None
+            }
+        }
+    }
+
+    // Removes the least element. If another thread removes it first, we try
+    // again with whichever element is now the least, so every element removed
+    // is returned to exactly one thread. Like remove, this only has shared
+    // access, so the element is returned by reference and its node is retired
+    // rather than freed; see pop_first for a version which returns it by value.
+    pub fn remove_first(&self) -> Option<&T> {
+        loop {
+            if let Some(elem) = self.remove(self.first()?) {
+                return Some(elem);
+            }
+        }
+    }
+
+    // Removes the greatest element, in the same way as remove_first.
+    pub fn remove_last(&self) -> Option<&T> {
+        loop {
+            if let Some(elem) = self.remove(self.last()?) {
+                return Some(elem);
+            }
+        }
+    }
+
+    // Removes the least element and returns it by value. As with take,
+    // exclusive access means its node can be freed immediately.
+    pub fn pop_first(&mut self) -> Option<T> {
+        let node = NonNull::from(self.nodes().next()?);
+        Some(self.take_node(node))
+    }
+
+    // Removes the greatest element and returns it by value.
+    pub fn pop_last(&mut self) -> Option<T> {
+        let node = self.last_node_before(None::<&T>, false)?;
+        Some(self.take_node(node))
+    }
+
+    fn take_node(&mut self, node: NonNull<Node<T>>) -> T {
+        remove::unlink(self, unsafe { &node.as_ref().inner.elem });
+        unsafe { (*node.as_ptr()).dealloc(&self.alloc) }
+    }
+
+    // Moves every element of `other` into this list, leaving `other` empty.
+    // The two lists are merged in order, reusing the nodes of this list; the
+    // elements of `other` are moved into nodes from this list's allocator,
+    // because the two allocators need not be related. Where both contain an
+    // equal element, the element from `other` is kept.
+    pub fn append(&mut self, other: &mut SkipList<T, H, A>) {
+        self.reclaim();
+        other.reclaim();
+        let mut ours = NodesMut::new(self.head()).peekable();
+        let mut theirs = NodesMut::new(other.head()).peekable();
+
+        for lane in &mut other.lanes {
+            lane.set_mut(ptr::null_mut());
+        }
+        other.current_height.set_mut(Self::INITIAL_HEIGHT);
+        other.len.set_mut(0);
+
+        let mut tails = Tails::new(&self.lanes);
+        let mut len = 0;
+        let mut height = Self::INITIAL_HEIGHT;
+
+        loop {
+            let node = match (ours.peek(), theirs.peek()) {
+                (Some(x), Some(y))  => match x.inner.elem.cmp(&y.inner.elem) {
+                    cmp::Ordering::Less     => ours.next().map(NonNull::from),
+                    cmp::Ordering::Greater  => theirs.next().map(|node| self.rehome(node, &other.alloc)),
+                    cmp::Ordering::Equal    => {
+                        unsafe { drop(ours.next().unwrap().dealloc(&self.alloc)) }
+                        theirs.next().map(|node| self.rehome(node, &other.alloc))
+                    }
+                }
+                (Some(_), None)     => ours.next().map(NonNull::from),
+                (None, Some(_))     => theirs.next().map(|node| self.rehome(node, &other.alloc)),
+                (None, None)        => break,
+            }.unwrap();
+
+            len += 1;
+            height = cmp::max(height, unsafe { node.as_ref().inner.height });
+            tails.push(node);
+        }
+
+        tails.finish();
+        self.len.set_mut(len);
+        self.current_height.set_mut(height);
     }
 }
 
-impl<T> SkipList<T> {
+impl<T, const H: usize, A: Allocator> SkipList<T, H, A> {
+    const INITIAL_HEIGHT: u8 = if H < 8 { H as u8 } else { 8 };
+
+    fn with_heights(heights: Heights, alloc: A) -> SkipList<T, H, A> {
+        const { assert!(H >= 1 && H <= 64, "the height of a SkipList must be between 1 and 64") };
+        SkipList {
+            current_height: AtomicU8::new(Self::INITIAL_HEIGHT),
+            len: Padded(AtomicUsize::new(0)),
+            heights,
+            branching: 1,
+            retired: Retired::new(),
+            stats: Stats::default(),
+            lanes: array::from_fn(|_| AtomicPtr::default()),
+            alloc,
+        }
+    }
+
+    // The current height is only ever raised to the height of a node, which
+    // is at most H, so this cannot underflow.
     fn lanes(&self) -> &[AtomicPtr<Node<T>>] {
-        let init = MAX_HEIGHT - self.current_height.load(Relaxed) as usize;
+        let height = self.current_height.load(Relaxed) as usize;
+        debug_assert!(height <= H, "current height exceeds the height of the list");
+        let init = H - height;
         &self.lanes[init..]
     }
 
-    pub fn get<'a, U: AbstractOrd<T> + ?Sized>(&'a self, elem: &U) -> Option<&T> {
-        get::get(self.lanes(), elem)
+    // The lanes from the current height down, or from `height` if it is
+    // higher, since a search for where to link or unlink a node must begin in
+    // the highest lane the node is in.
+    fn lanes_for(&self, height: usize) -> &[AtomicPtr<Node<T>>] {
+        let height = cmp::max(self.current_height.load(Relaxed) as usize, height);
+        &self.lanes[(H - height)..]
+    }
+
+    pub fn get<U: AbstractOrd<T> + ?Sized>(&self, elem: &U) -> Option<&T> {
+        get::get(self.lanes(), elem).map(|node| unsafe { &(*node.as_ptr()).inner.elem })
+    }
+
+    // Returns a mutable reference to the element equal to `elem`.
+    //
+    // The element must not be mutated in any way that changes how it is
+    // ordered relative to the other elements in the list. The list relies on
+    // its elements remaining sorted; if this is violated, later lookups,
+    // inserts and removals will behave incorrectly.
+    pub fn get_mut<U: AbstractOrd<T> + ?Sized>(&mut self, elem: &U) -> Option<&mut T> {
+        get::get(self.lanes(), elem).map(|node| unsafe { &mut (*node.as_ptr()).inner.elem })
+    }
+
+    // Returns mutable references to the elements equal to each of `elems`, or
+    // None if any of them is missing or two of them are equal to the same
+    // element. The same caution applies as for `get_mut`.
+    pub fn get_disjoint_mut<U, const N: usize>(&mut self, elems: [&U; N]) -> Option<[&mut T; N]>
+    where
+        U: AbstractOrd<T> + ?Sized,
+    {
+        let mut nodes: [NonNull<Node<T>>; N] = [NonNull::dangling(); N];
+        for (i, elem) in elems.iter().enumerate() {
+            let node = get::get(self.lanes(), *elem)?;
+            if nodes[..i].contains(&node) { return None; }
+            nodes[i] = node;
+        }
+        // Every node is distinct, so these references do not alias.
+        Some(nodes.map(|node| unsafe { &mut (*node.as_ptr()).inner.elem }))
+    }
+
+    pub fn contains<U: AbstractOrd<T> + ?Sized>(&self, elem: &U) -> bool {
+        self.get(elem).is_some()
+    }
+
+    pub fn range<U, R>(&self, range: R) -> Range<'_, T, U, R>
+    where
+        U: AbstractOrd<T> + ?Sized,
+        R: RangeBounds<U>,
+    {
+        let start = match range.start_bound() {
+            Included(elem)  => get::seek(self.lanes(), elem, true),
+            Excluded(elem)  => get::seek(self.lanes(), elem, false),
+            Unbounded       => self.head(),
+        };
+
+        Range { nodes: Nodes::new(start), range, _marker: PhantomData }
+    }
+
+    // Returns a cursor at the first element greater than or equal to `elem`.
+    pub fn cursor_at<U: AbstractOrd<T> + ?Sized>(&self, elem: &U) -> Cursor<'_, T, H, A> {
+        Cursor::new(self, elem)
+    }
+
+    pub fn first(&self) -> Option<&T> {
+        self.elems().next()
+    }
+
+    // The length is a counter, incremented when an element is linked into the
+    // lowest lane and decremented when one is removed, so reading it takes
+    // constant time and never synchronizes with other threads. While other
+    // threads are inserting or removing, it is only approximate: it may not
+    // yet count elements which have just been inserted or removed, so it can
+    // briefly disagree with what iterating the list finds.
+    pub fn len(&self) -> usize {
+        self.len.load(Relaxed)
+    }
+
+    // How often inserts into this list have lost races with other threads;
+    // see SkipListStats. The counts are kept with the `metrics` feature.
+    #[cfg(feature = "metrics")]
+    pub fn stats(&self) -> SkipListStats {
+        self.stats.get()
+    }
+
+    // The bottom lane contains every node, so the list is empty exactly when
+    // it contains no nodes which have not been removed.
+    pub fn is_empty(&self) -> bool {
+        self.nodes().next().is_none()
+    }
+
+    pub fn remove<U: AbstractOrd<T> + ?Sized>(&self, elem: &U) -> Option<&T> {
+        remove::remove(self, elem)
+    }
+
+    // Removes the element equal to `elem` and returns it by value. With
+    // exclusive access there are no other readers of the removed node, so it
+    // can be deallocated immediately rather than retired.
+    pub fn take<U: AbstractOrd<T> + ?Sized>(&mut self, elem: &U) -> Option<T> {
+        let node = get::get(self.lanes(), elem)?;
+        remove::unlink(self, elem);
+        unsafe { Some((*node.as_ptr()).dealloc(&self.alloc)) }
+    }
+
+    // Returns the number of elements in `range`. Like range, this seeks to
+    // the start of the range by descending the lanes of the list, so it takes
+    // time logarithmic in the length of the list and linear in the number of
+    // elements counted.
+    pub fn range_count<U, R>(&self, range: R) -> usize
+    where
+        U: AbstractOrd<T> + ?Sized,
+        R: RangeBounds<U>,
+    {
+        self.range(range).count()
+    }
+
+    // An approximation of the memory used by the list: the size of the list
+    // itself and of the allocation of each node, not counting any memory owned
+    // by the elements or the allocator's own overhead. This walks the whole
+    // list.
+    pub fn memory_usage(&self) -> usize {
+        let nodes: usize = self.nodes().map(|node| Node::<T>::layout(node.height()).size()).sum();
+        mem::size_of::<Self>() + nodes
     }
 
     pub fn elems(&self) -> Elems<'_, T> {
-        Elems { nodes: self.nodes() }
+        Elems { nodes: self.nodes(), lanes: self.lanes() }
+    }
+
+    // Iterates over the elements along with the number of lanes each is in.
+    pub fn towers(&self) -> Towers<'_, T> {
+        Towers { nodes: self.nodes() }
     }
 
     pub fn elems_mut(&mut self) -> ElemsMut<'_, T> {
-        ElemsMut { nodes: self.nodes_mut() }
+        ElemsMut { len: self.len(), nodes: self.nodes_mut() }
+    }
+
+    pub fn into_elems(mut self) -> IntoElems<T, A> {
+        self.reclaim();
+        let ptr = self.head();
+        let len = self.len();
+        // The nodes now belong to the iterator, so the list must not be
+        // dropped, but its allocator moves into the iterator.
+        let list = mem::ManuallyDrop::new(self);
+        let alloc = unsafe { ptr::read(&list.alloc) };
+        IntoElems { cursor: RawCursor::new(ptr), len, alloc }
+    }
+
+    pub fn clear(&mut self) {
+        self.reclaim();
+        for node in NodesMut::new(self.head()) {
+            unsafe { drop(node.dealloc(&self.alloc)) }
+        }
+        self.reset();
+    }
+
+    // Moves every element out of the list, in order. The list is emptied
+    // immediately, and can be used again once the iterator is dropped; any
+    // elements the iterator did not yield are dropped with it.
+    //
+    // This requires exclusive access for the same reason as clear: with only
+    // shared access, other threads may still hold references to the elements
+    // being freed, since nodes are never reclaimed while the list is shared.
+    // Nor could the head be detached atomically, as it is one pointer per
+    // lane; an insert racing the detaching could be linked into the old chain
+    // in one lane and the new in another.
+    pub fn drain(&mut self) -> Drain<'_, T, A> {
+        self.reclaim();
+        let ptr = self.head();
+        let len = self.len();
+        self.reset();
+        Drain { inner: IntoElems { cursor: RawCursor::new(ptr), len, alloc: &self.alloc } }
     }
 
-    pub fn into_elems(self) -> IntoElems<T> {
-        let ptr = self.first();
-        mem::forget(self);
-        IntoElems { ptr }
+    // Empty the list without freeing its nodes.
+    fn reset(&mut self) {
+        for lane in &mut self.lanes {
+            lane.set_mut(ptr::null_mut());
+        }
+        self.current_height.set_mut(Self::INITIAL_HEIGHT);
+        self.len.set_mut(0);
+    }
+
+    // Free the nodes which were removed while the list was shared. A removed
+    // node may still be linked into lanes which no search has passed through
+    // since it was removed, so every lane is walked to unlink the removed
+    // nodes first; with exclusive access, no node can be being removed
+    // concurrently. This is only done if any nodes have been retired, and the
+    // operations which call it walk the whole list anyway.
+    fn reclaim(&mut self) {
+        if self.retired.is_empty() { return; }
+
+        for level in 1..=H {
+            let mut pred = &self.lanes[H - level];
+            while let Some(node) = unsafe { unmark(pred.load(Relaxed)).as_ref() } {
+                let lane = &node.lanes()[node.height() - level];
+                match node.is_removed() {
+                    true    => pred.store(unmark(lane.load(Relaxed)), Relaxed),
+                    false   => pred = lane,
+                }
+            }
+        }
+
+        unsafe { self.retired.free(&self.alloc) }
+    }
+
+    // Moves every element greater than or equal to `elem` into a new list. In
+    // each lane, from the top of the list down, we find the last lane pointer
+    // which points before the split and move its successor to the head of the
+    // new list, terminating the lane there. Only the number of elements moved
+    // requires walking the new list.
+    pub fn split_off<U: AbstractOrd<T> + ?Sized>(&mut self, elem: &U) -> SkipList<T, H, A>
+    where
+        A: Clone,
+    {
+        self.reclaim();
+        let mut other = SkipList::with_heights(self.heights.clone(), self.alloc.clone());
+        other.branching = self.branching;
+        let mut lanes: &[AtomicPtr<Node<T>>] = &self.lanes[..];
+
+        for height in (1..=H).rev() {
+            let lane = loop {
+                let lane = &lanes[lanes.len() - height];
+                match unsafe { unmark(lane.load(Relaxed)).as_ref() } {
+                    Some(node) if elem.cmp(&node.inner.elem) == cmp::Ordering::Greater => {
+                        lanes = node.lanes();
+                    }
+                    _ => break lane,
+                }
+            };
+
+            // The mark on a lane belongs to the node the lane is in, so it is
+            // kept when the lane is terminated.
+            let next = lane.load(Relaxed);
+            lane.store(next.map_addr(|addr| addr & 1), Relaxed);
+            other.lanes[H - height].set_mut(unmark(next));
+        }
+
+        let len = other.nodes().count();
+        let remaining = self.len() - len;
+        self.len.set_mut(remaining);
+        other.len.set_mut(len);
+        other.current_height.set_mut(H as u8);
+        other.shrink_height();
+        self.shrink_height();
+        other
+    }
+
+    pub fn retain<F: FnMut(&T) -> bool>(&mut self, mut f: F) {
+        self.retain_mut(|elem| f(elem))
+    }
+
+    // Moves the elements for which `f` returns true out of the list, in order;
+    // see ExtractIf.
+    pub fn extract_if<F: FnMut(&T) -> bool>(&mut self, f: F) -> ExtractIf<'_, T, F, H, A> {
+        ExtractIf { raw: RawExtract::new(self), pred: f }
+    }
+
+    // Retaining is extracting the rejected elements and dropping them.
+    pub(crate) fn retain_mut<F: FnMut(&mut T) -> bool>(&mut self, mut f: F) {
+        let mut extract = RawExtract::new(self);
+        while extract.next(|elem| !f(elem)).is_some() { }
+    }
+
+    // Lower the current height past any empty lanes at the top of the head.
+    // This is only a hint to searches, which remain correct starting from any
+    // height, because every node is in the lowest lane. A concurrent insert may
+    // link a taller node after its lane was found empty, in which case searches
+    // start lower than they could until another node that tall is inserted.
+    fn shrink_height(&self) {
+        let mut height = self.current_height.load(Relaxed);
+        while height > Self::INITIAL_HEIGHT && self.lanes[H - height as usize].load(Acquire).is_null() {
+            match self.current_height.compare_exchange(height, height - 1, Relaxed, Relaxed) {
+                Ok(_)       => height -= 1,
+                Err(actual) => height = actual,
+            }
+        }
     }
 
     fn nodes(&self) -> Nodes<'_, T> {
-        Nodes::new(self.first())
+        Nodes::new(self.head())
     }
 
     fn nodes_mut(&mut self) -> NodesMut<'_, T> {
-        NodesMut::new(self.first())
+        NodesMut::new(self.head())
     }
 
-    fn first(&self) -> Ptr<Node<T>> {
-        NonNull::new(self.lanes[MAX_HEIGHT - 1].load(Acquire))
+    // Move the element of a node from another list's allocator into a new
+    // node of the same height from this list's allocator.
+    fn rehome<B: Allocator>(&self, node: &mut Node<T>, alloc: &B) -> NonNull<Node<T>> {
+        let height = node.height();
+        let elem = unsafe { node.dealloc(alloc) };
+        Node::alloc(elem, height, &self.current_height, &self.alloc)
+    }
+
+    fn head(&self) -> Ptr<Node<T>> {
+        NonNull::new(self.lanes[H - 1].load(Acquire))
     }
 }
 
 impl<T> Node<T> {
-    fn alloc(elem: T, max_height: &AtomicU8) -> NonNull<Node<T>> {
-        let height = random_height();
-        max_height.fetch_max(height as u8, Relaxed);
+    fn alloc<A: Allocator>(elem: T, height: usize, max_height: &AtomicU8, alloc: &A) -> NonNull<Node<T>> {
+        // Every insert allocates a node, but few raise the height, so we
+        // check before writing to avoid contending for the cache line.
+        let height = u8::try_from(height).expect("node height does not fit in u8");
+        if height > max_height.load(Relaxed) {
+            max_height.fetch_max(height, Relaxed);
+        }
         unsafe {
-            let layout = Node::<T>::layout(height);
-            let ptr = alloc::alloc_zeroed(layout) as *mut Node<T>;
-            (*ptr).inner.height = height as u8;
+            let layout = Node::<T>::layout(height as usize);
+            let ptr = match alloc.allocate_zeroed(layout) {
+                Some(ptr)   => ptr.as_ptr() as *mut Node<T>,
+                None        => handle_alloc_error(layout),
+            };
+            (*ptr).inner.height = height;
+            // Zeroed memory is a valid null AtomicPtr, but not under loom.
+            #[cfg(loom)]
+            for i in 0..height as usize {
+                let lanes = ptr::addr_of_mut!((*ptr).lanes) as *mut AtomicPtr<Node<T>>;
+                ptr::write(lanes.add(i), AtomicPtr::default());
+            }
             ptr::write(&mut (*ptr).inner.elem as *mut T, elem);
             NonNull::new_unchecked(ptr)
         }
     }
 
-    unsafe fn dealloc(&mut self) -> T {
+    // Safety: the node must have been allocated by `alloc`, or a clone of it.
+    unsafe fn dealloc<A: Allocator>(&mut self, alloc: &A) -> T {
         let layout = Node::<T>::layout(self.height());
-        let elem = ptr::read(&mut self.inner.elem);
-        alloc::dealloc(self as *mut Node<T> as *mut u8, layout);
+        let elem = ptr::read(&self.inner.elem);
+        alloc.deallocate(NonNull::from(self).cast(), layout);
         elem
     }
 
     fn next(&self) -> Ptr<Node<T>> {
-        NonNull::new(self.lanes().last().unwrap().load(Acquire))
+        NonNull::new(unmark(self.lanes().last().unwrap().load(Acquire)))
     }
 
-    fn lanes(&self) -> &[AtomicPtr<Node<T>>] {
-        #[repr(C)]
-        struct LanesPtr<T> {
-            lanes: *const Lanes<T>,
-            height: usize,
-        }
+    // A node has been removed once its lowest lane has been marked.
+    fn is_removed(&self) -> bool {
+        marked(self.lanes().last().unwrap().load(Acquire))
+    }
 
-        let lanes = &self.lanes as *const Lanes<T>;
-        let height = self.height();
-        unsafe { mem::transmute(LanesPtr { lanes, height }) }
+    // The node was allocated with `height` lanes in place of the one in its
+    // type, so the lanes are read as a slice of that length.
+    fn lanes(&self) -> &[AtomicPtr<Node<T>>] {
+        let lanes = ptr::addr_of!(self.lanes) as *const AtomicPtr<Node<T>>;
+        unsafe { slice::from_raw_parts(lanes, self.height()) }
     }
 
     fn height(&self) -> usize {
         self.inner.height as usize
     }
 
-    fn layout(height: usize) -> alloc::Layout {
-        let size = ((height + 1) * mem::size_of::<usize>()) + mem::size_of::<T>();
-        let align = cmp::max(mem::align_of::<T>(), mem::align_of::<usize>());
-        unsafe {
-            alloc::Layout::from_size_align_unchecked(size, align)
-        }
+    // The layout of a node is the layout of the repr(C) Node struct, but with
+    // `height` lanes in place of one. Extending the layout of the inner node
+    // accounts for padding before the lanes, and padding the result accounts
+    // for over-aligned elements.
+    fn layout(height: usize) -> Layout {
+        let inner = Layout::new::<InnerNode<T>>();
+        let lanes = Layout::array::<AtomicPtr<Node<T>>>(height).unwrap();
+        inner.extend(lanes).unwrap().0.pad_to_align()
     }
 }
 
-impl<T: fmt::Debug> fmt::Debug for SkipList<T> {
+impl<T: fmt::Debug, const H: usize, A: Allocator> fmt::Debug for SkipList<T, H, A> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         f.debug_list().entries(self.nodes()).finish()
     }
@@ -156,16 +864,39 @@ impl<T: fmt::Debug> fmt::Debug for Node<T> {
     }
 }
 
-impl<T> Drop for SkipList<T> {
-    fn drop(&mut self) {
-        // TODO call destructors
-        for node in self.nodes_mut() {
-            unsafe { drop(node.dealloc()) }
+impl<T: AbstractOrd<T>, const H: usize> Default for SkipList<T, H> {
+    fn default() -> Self {
+        SkipList::with_height()
+    }
+}
+
+// The clone has exactly the same structure as the original, with each node
+// having the same height, so it can be built in a single pass.
+impl<T: Clone, const H: usize, A: Allocator + Clone> Clone for SkipList<T, H, A> {
+    fn clone(&self) -> Self {
+        let mut list = SkipList::with_heights(self.heights.clone(), self.alloc.clone());
+        list.branching = self.branching;
+        let mut tails = Tails::new(&list.lanes);
+        let mut len = 0;
+
+        for node in self.nodes() {
+            len += 1;
+            tails.push(Node::alloc(node.inner.elem.clone(), node.height(), &list.current_height, &list.alloc));
         }
+
+        tails.finish();
+        list.len.set_mut(len);
+        list
     }
 }
 
-impl<T: AbstractOrd<T>> Extend<T> for SkipList<T> {
+impl<T, const H: usize, A: Allocator> Drop for SkipList<T, H, A> {
+    fn drop(&mut self) {
+        self.clear();
+    }
+}
+
+impl<T: AbstractOrd<T>, const H: usize, A: Allocator> Extend<T> for SkipList<T, H, A> {
     fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
         iter.into_iter().for_each(|elem| {
             self.insert(elem);
@@ -173,7 +904,7 @@ impl<T: AbstractOrd<T>> Extend<T> for SkipList<T> {
     }
 }
 
-impl<'a, T: AbstractOrd<T> + Copy> Extend<&'a T> for SkipList<T> {
+impl<'a, T: AbstractOrd<T> + Copy, const H: usize, A: Allocator> Extend<&'a T> for SkipList<T, H, A> {
     fn extend<I: IntoIterator<Item = &'a T>>(&mut self, iter: I) {
         iter.into_iter().for_each(|&elem| {
             self.insert(elem);
@@ -181,19 +912,53 @@ impl<'a, T: AbstractOrd<T> + Copy> Extend<&'a T> for SkipList<T> {
     }
 }
 
-impl<T: AbstractOrd<T>> FromIterator<T> for SkipList<T> {
+impl<T: AbstractOrd<T>, const H: usize> FromIterator<T> for SkipList<T, H> {
     fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
-        let mut list = Self::new();
+        let mut list = Self::with_height();
         list.extend(iter);
         list
     }
 }
 
-fn random_height() -> usize {
-    const MASK: u32 = 1 << (MAX_HEIGHT - 1);
-    1 + (rand::random::<u32>() | MASK).trailing_zeros() as usize
+// Lane pointers are marked when the node they belong to is being removed. Nodes
+// are at least pointer aligned, so the lowest bit of a pointer to a node is
+// always free to hold the mark.
+fn marked<T>(ptr: *mut Node<T>) -> bool {
+    ptr.addr() & 1 == 1
+}
+
+fn with_mark<T>(ptr: *mut Node<T>) -> *mut Node<T> {
+    ptr.map_addr(|addr| addr | 1)
+}
+
+fn unmark<T>(ptr: *mut Node<T>) -> *mut Node<T> {
+    ptr.map_addr(|addr| addr & !1)
+}
+
+// The alignment of a node is at least that of its lanes, whatever its element.
+const _: () = assert!(mem::align_of::<Node<u8>>() >= 2);
+
+#[test]
+fn test_mark() {
+    let list: SkipList<i32> = (0..3).collect();
+    let node = list.head().unwrap().as_ptr();
+    assert!(!marked(node));
+    assert!(marked(with_mark(node)));
+    assert_eq!(unmark(with_mark(node)), node);
+    assert_eq!(unmark(node), node);
+    assert_eq!(with_mark(with_mark(node)), with_mark(node));
+    assert!(!marked(ptr::null_mut::<Node<i32>>()));
+    assert!(unmark(with_mark(ptr::null_mut::<Node<i32>>())).is_null());
+
+    // Removing a node marks its lanes, so its pointer to its successor must
+    // be unmarked to be followed.
+    list.remove(&0);
+    let lowest = unsafe { (*node).lanes().last().unwrap().load(Acquire) };
+    assert!(marked(lowest));
+    assert_eq!(unsafe { (*unmark(lowest)).inner.elem }, 1);
 }
 
+#[cfg(feature = "std")]
 #[test]
 fn test() {
     #[derive(Debug, Ord, PartialOrd, Eq, PartialEq)] struct DropInt(i32);
@@ -201,15 +966,551 @@ fn test() {
         println!("Dropping {}", self.0);
     } }
     let list = SkipList::new();
+    assert!(list.is_empty());
     list.insert(DropInt(1));
+    assert!(!list.is_empty());
     list.insert(DropInt(3));
     list.insert(DropInt(0));
     list.insert(DropInt(5));
     list.insert(DropInt(2));
     assert!(list.insert(DropInt(3)).is_some());
     list.insert(DropInt(4));
+    assert_eq!(list.len(), 6);
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn test_range() {
+    use std::ops::Bound;
+
+    let list: SkipList<i32> = (0..10).map(|x| x * 2).collect();
+    let range = |r: (Bound<i32>, Bound<i32>)| list.range(r).cloned().collect::<Vec<_>>();
+
+    assert_eq!(range((Included(4), Excluded(10))), [4, 6, 8]);
+    assert_eq!(range((Excluded(4), Included(10))), [6, 8, 10]);
+    assert_eq!(range((Included(3), Included(7))), [4, 6]);
+    assert_eq!(range((Excluded(3), Excluded(7))), [4, 6]);
+    assert_eq!(range((Unbounded, Excluded(4))), [0, 2]);
+    assert_eq!(range((Excluded(14), Unbounded)), [16, 18]);
+    assert_eq!(range((Unbounded, Unbounded)).len(), 10);
+    assert_eq!(range((Excluded(4), Excluded(6))), Vec::<i32>::new());
+    assert_eq!(range((Included(8), Included(4))), Vec::<i32>::new());
+    assert_eq!(range((Included(20), Unbounded)), Vec::<i32>::new());
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn test_get_or_insert() {
+    let list = SkipList::new();
+    let a: &i32 = list.get_or_insert(1);
+    let b: &i32 = list.get_or_insert(1);
+    assert!(std::ptr::eq(a, b));
+    assert_eq!(list.get_or_insert(2), &2);
+    assert_eq!(list.len(), 2);
 }
 
+#[test]
+fn test_first_last() {
+    let mut list: SkipList<i32> = SkipList::new();
+    assert_eq!((list.first(), list.last()), (None, None));
+    list.extend(&[3, 1, 4, 5, 9, 2, 6]);
+    assert_eq!((list.first(), list.last()), (Some(&1), Some(&9)));
+    list.remove(&9);
+    list.remove(&6);
+    list.remove(&1);
+    assert_eq!((list.first(), list.last()), (Some(&2), Some(&5)));
+}
+
+#[test]
+fn test_contains() {
+    let list: SkipList<u32> = (0..10).collect();
+    assert!(list.contains(&3));
+    assert!(!list.contains(&10));
+}
+
+#[test]
+fn test_height() {
+    let short: SkipList<i32, 2> = (0..1000).collect();
+    let tall: SkipList<i32, 64> = (0..1000).collect();
+    assert!(short.nodes().all(|node| node.height() <= 2));
+    assert!(short.elems().cloned().eq(0..1000));
+    assert!(tall.elems().cloned().eq(0..1000));
+    assert_eq!(short.get(&500), Some(&500));
+    assert_eq!(tall.get(&500), Some(&500));
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn test_get_disjoint_mut() {
+    let mut list: SkipList<i32> = (0..10).collect();
+    let [a, b] = list.get_disjoint_mut([&2, &7]).unwrap();
+    std::mem::swap(a, b);
+    assert!(list.get_disjoint_mut([&1, &1]).is_none());
+    assert!(list.get_disjoint_mut([&1, &10]).is_none());
+    assert_eq!(list.get_disjoint_mut::<i32, 0>([]), Some([]));
+    assert!(list.elems().copied().eq([0, 1, 7, 3, 4, 5, 6, 2, 8, 9]));
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn test_try_insert_ref() {
+    use std::cell::Cell;
+
+    thread_local!(static CLONES: Cell<usize> = const { Cell::new(0) });
+
+    #[derive(PartialEq, Eq, PartialOrd, Ord)] struct Counted(i32);
+    impl Clone for Counted { fn clone(&self) -> Counted {
+        CLONES.with(|clones| clones.set(clones.get() + 1));
+        Counted(self.0)
+    } }
+
+    let list: SkipList<Counted> = (0..10).map(Counted).collect();
+    assert_eq!(list.try_insert_ref(&Counted(5)).map(|x| x.0), Some(5));
+    assert_eq!(CLONES.with(Cell::get), 0);
+    assert!(list.try_insert_ref(&Counted(10)).is_none());
+    assert_eq!(CLONES.with(Cell::get), 1);
+    assert_eq!(list.len(), 11);
+}
+
+#[test]
+fn test_towers() {
+    let list: SkipList<i32, 4> = (0..1000).collect();
+    assert!(list.towers().map(|(&elem, _)| elem).eq(0..1000));
+    let heights: Vec<u8> = list.towers().map(|(_, height)| height).collect();
+    assert!(heights.iter().all(|&height| (1..=4).contains(&height)));
+    assert!(heights.contains(&4));
+}
+
+#[test]
+fn test_with_capacity() {
+    let mut list: SkipList<i32> = SkipList::with_capacity(10_000_000);
+    assert_eq!(list.current_height.load(Relaxed), 24);
+    list.extend(0..100);
+    assert!(list.elems().copied().eq(0..100));
+    assert_eq!(list.get(&50), Some(&50));
+
+    let small: SkipList<i32, 4> = SkipList::with_capacity(10_000_000);
+    assert_eq!(small.current_height.load(Relaxed), 4);
+    let empty: SkipList<i32> = SkipList::with_capacity(0);
+    assert_eq!(empty.current_height.load(Relaxed), 8);
+}
+
+#[test]
+fn test_shrink_height() {
+    let mut list: SkipList<i32> = SkipList::with_seed(0);
+    list.extend(0..10_000);
+    assert!(list.current_height.load(Relaxed) > SkipList::<i32>::INITIAL_HEIGHT);
+
+    for x in 0..10_000 {
+        list.remove(&x);
+    }
+    assert_eq!(list.current_height.load(Relaxed), SkipList::<i32>::INITIAL_HEIGHT);
+
+    list.extend(0..10_000);
+    list.retain(|&x| x < 10);
+    assert_eq!(list.current_height.load(Relaxed), SkipList::<i32>::INITIAL_HEIGHT);
+    assert!(list.elems().copied().eq(0..10));
+}
+
+#[test]
+fn test_insert_above_current_height() {
+    // Find a seed whose first height is the greatest the list allows, and
+    // insert with it into a list which is still at its initial height.
+    let seed = (0..).find(|&seed| Heights::seeded(seed).next(16) == 16).unwrap();
+    let mut list: SkipList<i32, 16> = SkipList::with_seed(0);
+    list.extend([0, 2, 4]);
+    assert_eq!(list.current_height.load(Relaxed), 8);
+
+    list.heights = Heights::seeded(seed);
+    assert!(list.insert(3).is_none());
+    assert_eq!(list.current_height.load(Relaxed), 16);
+
+    // The node is linked into the highest lane of the head, which was unused.
+    let node = list.nodes().find(|node| node.inner.elem == 3).unwrap();
+    assert_eq!(node.height(), 16);
+    assert!(ptr::eq(list.lanes[0].load(Acquire), node));
+
+    list.extend([1, 5]);
+    assert!(list.elems().copied().eq(0..6));
+    assert!((0..6).all(|x| list.get(&x) == Some(&x)));
+}
+
+#[test]
+fn test_layout() {
+    #[derive(Ord, PartialOrd, Eq, PartialEq, Debug)]
+    #[repr(align(128))]
+    struct Aligned(i32);
+
+    let list: SkipList<Aligned> = (0..100).map(Aligned).collect();
+    assert!(list.elems().all(|elem| (elem as *const Aligned as usize).is_multiple_of(128)));
+    assert!(list.elems().map(|elem| elem.0).eq(0..100));
+    assert_eq!(list.get(&Aligned(50)), Some(&Aligned(50)));
+
+    let list: SkipList<()> = SkipList::new();
+    assert!(list.insert(()).is_none());
+    assert!(list.insert(()).is_some());
+    assert_eq!(list.get(&()), Some(&()));
+    assert_eq!(list.len(), 1);
+
+    // Besides its lanes, a node stores only its element and its height.
+    assert_eq!(mem::size_of::<InnerNode<()>>(), 1);
+    assert_eq!(mem::size_of::<InnerNode<u64>>(), 8 + mem::align_of::<u64>());
+}
+
+#[test]
+fn test_memory_usage() {
+    let mut list: SkipList<u64> = SkipList::new();
+    let empty = list.memory_usage();
+    assert_eq!(empty, mem::size_of::<SkipList<u64>>());
+
+    list.extend(0..100);
+    let minimum = 100 * (mem::size_of::<InnerNode<u64>>() + mem::size_of::<usize>());
+    assert!(list.memory_usage() >= empty + minimum);
+}
+
+#[test]
+fn test_split_off() {
+    let mut list: SkipList<i32> = (0..1000).collect();
+    list.remove(&600);
+    let other = list.split_off(&500);
+    assert!(list.elems().copied().eq(0..500));
+    assert!(other.elems().copied().eq((500..1000).filter(|&x| x != 600)));
+    assert_eq!(list.len(), 500);
+    assert_eq!(other.len(), 499);
+    assert_eq!(list.get(&499), Some(&499));
+    assert_eq!(list.get(&500), None);
+    assert_eq!(other.get(&500), Some(&500));
+    assert!(list.insert(750).is_none());
+    assert!(other.insert(250).is_none());
+
+    let other = list.split_off(&2000);
+    assert!(other.is_empty());
+    assert_eq!(list.len(), 501);
+    let other = list.split_off(&0);
+    assert!(list.is_empty());
+    assert_eq!(other.len(), 501);
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn test_take() {
+    use crate::QWrapper;
+
+    let mut list: SkipList<String> = ["a", "b", "c"].iter().map(|s| s.to_string()).collect();
+    assert_eq!(list.take(QWrapper::new("b")), Some(String::from("b")));
+    assert_eq!(list.take(QWrapper::new("b")), None);
+    assert_eq!(list.len(), 2);
+    assert!(list.elems().eq(["a", "c"]));
+}
+
+#[test]
+fn test_replace() {
+    #[derive(Debug)]
+    struct Entry(i32, &'static str);
+
+    impl AbstractOrd<Entry> for Entry {
+        fn cmp(&self, other: &Entry) -> cmp::Ordering {
+            Ord::cmp(&self.0, &other.0)
+        }
+    }
+
+    let mut list: SkipList<Entry> = SkipList::new();
+    assert!(list.replace(Entry(0, "a")).is_none());
+    assert!(list.replace(Entry(1, "b")).is_none());
+    assert_eq!(list.replace(Entry(0, "c")).map(|e| e.1), Some("a"));
+    assert_eq!(list.len(), 2);
+    assert_eq!(list.get(&Entry(0, "")).map(|e| e.1), Some("c"));
+}
+
+#[test]
+fn test_range_count() {
+    let list: SkipList<i32> = (0..100).map(|x| x * 2).collect();
+    let ranges: [(ops::Bound<i32>, ops::Bound<i32>); 6] = [
+        (Included(10), Excluded(20)),
+        (Included(10), Included(20)),
+        (Included(11), Excluded(21)),
+        (Excluded(10), Unbounded),
+        (Unbounded, Unbounded),
+        (Included(20), Excluded(10)),
+    ];
+    for (range, count) in ranges.iter().zip([5, 6, 5, 94, 100, 0]) {
+        assert_eq!(list.range_count(*range), count);
+    }
+}
+
+#[test]
+fn test_range_count_modified() {
+    let list: SkipList<i32> = (0..1000).collect();
+    for x in (500..600).step_by(2) {
+        list.remove(&x);
+    }
+    list.insert(-1);
+    list.insert(2000);
+    assert_eq!(list.range_count(500..600), 50);
+    assert_eq!(list.range_count(550..), 426);
+    assert_eq!(list.range_count(..=0), 2);
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn test_clear() {
+    use std::sync::atomic::AtomicUsize;
+
+    static DROPS: AtomicUsize = AtomicUsize::new(0);
+    #[derive(Ord, PartialOrd, Eq, PartialEq)] struct DropInt(i32);
+    impl Drop for DropInt { fn drop(&mut self) {
+        DROPS.fetch_add(1, Relaxed);
+    } }
+
+    let mut list: SkipList<DropInt> = (0..100).map(DropInt).collect();
+    list.clear();
+    assert!(list.is_empty());
+    assert_eq!(list.len(), 0);
+    assert_eq!(DROPS.load(Relaxed), 100);
+    list.insert(DropInt(7));
+    assert_eq!(list.get(&DropInt(7)).map(|x| x.0), Some(7));
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn test_retain() {
+    use std::sync::atomic::AtomicUsize;
+
+    static DROPS: AtomicUsize = AtomicUsize::new(0);
+    #[derive(Ord, PartialOrd, Eq, PartialEq)] struct DropInt(i32);
+    impl Drop for DropInt { fn drop(&mut self) {
+        DROPS.fetch_add(1, Relaxed);
+    } }
+
+    let mut list: SkipList<DropInt> = (0..100).map(DropInt).collect();
+    list.retain(|x| x.0 % 2 == 0);
+    assert_eq!(DROPS.load(Relaxed), 50);
+    assert_eq!(list.len(), 50);
+    assert!(list.elems().map(|x| x.0).eq((0..100).step_by(2)));
+    drop(list);
+    assert_eq!(DROPS.load(Relaxed), 100);
+
+    let mut list: SkipList<i32> = (0..1000).collect();
+    list.retain(|x| x % 3 == 0);
+    assert!((0..1000).all(|x| list.contains(&x) == (x % 3 == 0)));
+    list.insert(1);
+    assert_eq!(list.elems().take(4).collect::<Vec<_>>(), [&0, &1, &3, &6]);
+    assert_eq!(list.len(), 335);
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn test_extract_if_forget() {
+    use std::sync::atomic::AtomicUsize;
+
+    static DROPS: AtomicUsize = AtomicUsize::new(0);
+    #[derive(Ord, PartialOrd, Eq, PartialEq)] struct DropInt(i32);
+    impl Drop for DropInt { fn drop(&mut self) {
+        DROPS.fetch_add(1, Relaxed);
+    } }
+
+    let mut list: SkipList<DropInt> = (0..100).map(DropInt).collect();
+    let mut extract = list.extract_if(|x| x.0 % 2 == 1);
+    assert_eq!(extract.next().map(|x| x.0), Some(1));
+    mem::forget(extract);
+    assert_eq!(DROPS.load(Relaxed), 1);
+
+    // The nodes the extraction had not reached are leaked.
+    assert_eq!(list.len(), 1);
+    assert!(list.elems().map(|x| x.0).eq([0]));
+    list.insert(DropInt(50));
+    list.insert(DropInt(-1));
+    assert!(list.elems().map(|x| x.0).eq([-1, 0, 50]));
+    drop(list);
+    assert_eq!(DROPS.load(Relaxed), 4);
+}
+
+#[test]
+fn test_from_sorted_iter() {
+    let list: SkipList<i32> = SkipList::from_sorted_iter(0..100);
+    assert_eq!(list.len(), 100);
+    assert!(list.elems().copied().eq(0..100));
+    assert!((0..100).all(|x| list.contains(&x)));
+    assert!(list.insert(50).is_some());
+    assert!(list.insert(100).is_none());
+}
+
+#[test]
+#[should_panic]
+#[cfg(debug_assertions)]
+fn test_from_sorted_iter_unsorted() {
+    let _: SkipList<i32> = SkipList::from_sorted_iter([0, 2, 1]);
+}
+
+#[test]
+fn test_from_elems_with_heights() {
+    let list: SkipList<i32> = (0..100).collect();
+    let rebuilt: SkipList<i32> = SkipList::from_elems_with_heights(list.towers().map(|(&x, height)| (x, height)));
+    assert!(rebuilt.towers().eq(list.towers()));
+    assert_eq!(rebuilt.len(), 100);
+    assert_eq!(rebuilt.get(&42), Some(&42));
+    rebuilt.check_invariants();
+}
+
+#[test]
+#[should_panic]
+fn test_from_elems_with_heights_invalid() {
+    let _: SkipList<i32, 4> = SkipList::from_elems_with_heights([(0, 1), (1, 5)]);
+}
+
+#[test]
+fn test_branching() {
+    let mut list: SkipList<i32> = SkipList::with_branching(3);
+    list.extend(0..10_000);
+    assert!(list.elems().copied().eq(0..10_000));
+    // With p = 1/8, about one node in eight is taller than one lane.
+    let tall = list.towers().filter(|&(_, height)| height > 1).count();
+    assert!((1_000..1_500).contains(&tall));
+    assert_eq!(list.clone().branching, 3);
+}
+
+#[test]
+fn test_seed() {
+    let heights = |seed| {
+        let list: SkipList<i32> = SkipList::with_seed(seed);
+        for x in [5, 3, 8, 1, 9, 2, 7, 4, 6, 0] {
+            list.insert(x);
+        }
+        list.nodes().map(|node| node.height()).collect::<Vec<_>>()
+    };
+    assert_eq!(heights(1), heights(1));
+    assert_ne!(heights(1), heights(2));
+}
+
+#[test]
+fn test_height_source() {
+    // Two trailing zeros put every node in three lanes.
+    let list: SkipList<i32, 4> = SkipList::with_height_source(|| 0b100);
+    for x in [5, 3, 8, 1, 9] {
+        list.insert(x);
+    }
+    assert!(list.towers().all(|(_, height)| height == 3));
+
+    // Clones keep the source.
+    let clone = list.clone();
+    clone.insert(4);
+    assert!(clone.towers().all(|(_, height)| height == 3));
+}
+
+#[test]
+fn test_tall_nodes() {
+    // Nodes taller than the current height raise it before they are linked,
+    // and are unlinked from every lane when they are removed.
+    let list: SkipList<i32, 16> = SkipList::with_height_source(|| 1 << 12);
+    for x in (0..10).rev() {
+        list.insert(x);
+    }
+    assert!(list.towers().all(|(_, height)| height == 13));
+    list.check_invariants();
+    for x in 0..10 {
+        assert_eq!(list.remove(&x), Some(&x));
+        list.check_invariants();
+    }
+    assert!(list.is_empty());
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn test_seed_threads() {
+    use std::sync::mpsc;
+    use std::thread;
+
+    // Two threads take turns inserting, passing a token back and forth, so
+    // they are scheduled the same way every time.
+    let towers = || {
+        let list: SkipList<i32> = SkipList::with_seed(7);
+        let (to_b, from_a) = mpsc::channel();
+        let (to_a, from_b) = mpsc::channel();
+        thread::scope(|scope| {
+            let list = &list;
+            scope.spawn(move || for x in (0..20).step_by(2) {
+                list.insert(x);
+                to_b.send(()).unwrap();
+                from_b.recv().unwrap();
+            });
+            scope.spawn(move || for x in (1..20).step_by(2) {
+                from_a.recv().unwrap();
+                list.insert(x);
+                to_a.send(()).unwrap();
+            });
+        });
+        list.towers().map(|(&x, height)| (x, height)).collect::<Vec<_>>()
+    };
+    assert_eq!(towers(), towers());
+}
+
+#[test]
+fn test_get_le_ge() {
+    let list: SkipList<i32> = (0..10).map(|x| x * 2).collect();
+    assert_eq!((list.get_le(&7), list.get_ge(&7)), (Some(&6), Some(&8)));
+    assert_eq!((list.get_le(&8), list.get_ge(&8)), (Some(&8), Some(&8)));
+    assert_eq!((list.get_le(&-1), list.get_ge(&-1)), (None, Some(&0)));
+    assert_eq!((list.get_le(&19), list.get_ge(&19)), (Some(&18), None));
+    list.remove(&6);
+    list.remove(&8);
+    assert_eq!((list.get_le(&8), list.get_ge(&6)), (Some(&4), Some(&10)));
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn test_clone() {
+    let list: SkipList<i32> = (0..100).collect();
+    list.remove(&50);
+    let clone = list.clone();
+    assert!(clone.nodes().map(Node::height).eq(list.nodes().map(Node::height)));
+    assert!(clone.elems().eq(list.elems()));
+    assert_eq!(clone.len(), 99);
+    clone.insert(50);
+    clone.remove(&0);
+    assert_eq!(clone.get(&50), Some(&50));
+    assert_eq!(list.get(&50), None);
+    assert_eq!(list.get(&0), Some(&0));
+    assert!(!std::ptr::eq(clone.get(&1).unwrap(), list.get(&1).unwrap()));
+}
+
+#[test]
+fn test_rev() {
+    let mut list: SkipList<i32> = (0..10).collect();
+    list.remove(&9);
+    list.remove(&4);
+    assert!(list.elems_mut().rev().map(|x| *x).eq([8, 7, 6, 5, 3, 2, 1, 0]));
+
+    let mut elems = list.elems_mut();
+    assert_eq!(elems.next_back(), Some(&mut 8));
+    assert_eq!(elems.next(), Some(&mut 0));
+    assert_eq!(elems.next_back(), Some(&mut 7));
+    assert!(elems.map(|x| *x).eq([1, 2, 3, 5, 6]));
+
+    assert_eq!(list.elems_mut().len(), 8);
+    let mut elems = list.into_elems();
+    assert_eq!(elems.next(), Some(0));
+    assert_eq!(elems.next_back(), Some(8));
+    assert_eq!(elems.len(), 6);
+    assert!(elems.rev().eq([7, 6, 5, 3, 2, 1]));
+}
+
+#[test]
+fn test_remove() {
+    let list: SkipList<i32> = (0..10).collect();
+    assert_eq!(list.remove(&4), Some(&4));
+    assert_eq!(list.remove(&4), None);
+    assert_eq!(list.remove(&10), None);
+    assert_eq!(list.get(&4), None);
+    assert_eq!(list.len(), 9);
+    assert!(list.insert(4).is_none());
+    assert_eq!(list.get(&4), Some(&4));
+    assert_eq!(list.remove(&0), Some(&0));
+    assert_eq!(list.remove(&9), Some(&9));
+    assert_eq!(list.elems().cloned().collect::<Vec<_>>(), [1, 2, 3, 4, 5, 6, 7, 8]);
+    assert_eq!(list.into_elems().collect::<Vec<_>>(), [1, 2, 3, 4, 5, 6, 7, 8]);
+}
+
+#[cfg(feature = "std")]
 #[test]
 fn test_concurrent() {
     const THREADS: i32 = 16;
@@ -235,7 +1536,235 @@ fn test_concurrent() {
         h.join().unwrap();
     }
 
+    assert_eq!(list.len(), ELEMS as usize);
+
     for (&elem, expected) in list.elems().zip(0..ELEMS) {
         assert_eq!(elem, expected);
     }
 }
+
+#[cfg(feature = "std")]
+#[test]
+fn test_concurrent_order() {
+    use crate::Map;
+
+    const THREADS: usize = 8;
+    const ELEMS: usize = 20_000;
+    let list: std::sync::Arc<SkipList<usize>> = std::sync::Arc::new(SkipList::new());
+    let map: std::sync::Arc<Map<usize, usize>> = std::sync::Arc::new(Map::new());
+
+    // Each thread inserts its share of a shuffled range: multiplying by a
+    // number coprime to ELEMS permutes it.
+    let handles: Vec<_> = (0..THREADS).map(|thread| {
+        let (list, map) = (list.clone(), map.clone());
+        std::thread::spawn(move || {
+            for i in (thread..ELEMS).step_by(THREADS) {
+                let x = i * 7919 % ELEMS;
+                list.insert(x);
+                map.insert(x, i);
+            }
+        })
+    }).collect();
+    for handle in handles {
+        handle.join().unwrap();
+    }
+
+    list.check_invariants();
+    assert!(list.elems().zip(list.elems().skip(1)).all(|(x, y)| x < y));
+    assert!(list.elems().copied().eq(0..ELEMS));
+    assert!(map.keys().zip(map.keys().skip(1)).all(|(x, y)| x < y));
+    assert_eq!(map.len(), ELEMS);
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn test_check_invariants() {
+    let mut list: SkipList<i32> = (0..100).collect();
+    list.check_invariants();
+    list.remove(&50);
+    list.check_invariants();
+    list.retain(|x| x % 3 != 0);
+    list.check_invariants();
+
+    // Swapping two elements breaks the order of every lane they are in.
+    let (a, b) = {
+        let mut nodes = list.nodes_mut();
+        (&mut nodes.next().unwrap().inner.elem as *mut i32, &mut nodes.next().unwrap().inner.elem as *mut i32)
+    };
+    unsafe { ptr::swap(a, b) }
+    let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| list.check_invariants()));
+    assert!(result.is_err());
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn test_pop() {
+    let mut list: SkipList<String> = (0..10).map(|x| x.to_string()).collect();
+    list.remove(&String::from("0"));
+    list.remove(&String::from("9"));
+    assert_eq!(list.pop_first().as_deref(), Some("1"));
+    assert_eq!(list.pop_last().as_deref(), Some("8"));
+    assert_eq!(list.len(), 6);
+    list.check_invariants();
+    while list.pop_last().is_some() { }
+    assert!(list.is_empty());
+    assert_eq!(list.pop_first(), None);
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn test_concurrent_remove_first_last() {
+    const THREADS: i32 = 8;
+    const ELEMS: i32 = 10_000;
+    let list: std::sync::Arc<SkipList<i32>> = std::sync::Arc::new((0..ELEMS).collect());
+    let mut handles = vec![];
+    for thread in 0..THREADS {
+        let list = list.clone();
+        handles.push(std::thread::spawn(move || {
+            let mut popped = vec![];
+            loop {
+                let elem = match thread % 2 {
+                    0   => list.remove_first(),
+                    _   => list.remove_last(),
+                };
+                match elem {
+                    Some(&elem) => popped.push(elem),
+                    None        => return popped,
+                }
+            }
+        }));
+    }
+
+    let mut popped: Vec<i32> = handles.into_iter().flat_map(|h| h.join().unwrap()).collect();
+    popped.sort();
+    assert!(popped.into_iter().eq(0..ELEMS));
+    assert!(list.is_empty());
+    assert_eq!(list.len(), 0);
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn test_concurrent_remove() {
+    const THREADS: i32 = 16;
+    const ELEMS: i32 = 100_000;
+    let list = std::sync::Arc::new(SkipList::new());
+    let mut handles = vec![];
+    for offset in 0..THREADS {
+        let list = list.clone();
+        handles.push(std::thread::spawn(move || {
+            for x in (0..ELEMS).filter(|x| x % THREADS == offset) {
+                list.insert(x);
+                if (x / THREADS) % 2 == 1 {
+                    assert_eq!(list.remove(&x), Some(&x));
+                }
+            }
+        }));
+    }
+
+    for h in handles {
+        h.join().unwrap();
+    }
+
+    let expected = (0..ELEMS).filter(|x| (x / THREADS) % 2 == 0);
+    assert_eq!(list.len(), expected.clone().count());
+    assert!(list.elems().cloned().eq(expected));
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn test_concurrent_insert_remove_get() {
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering::SeqCst};
+
+    // Counts the elements which have been dropped, to check that every
+    // element is dropped exactly once.
+    struct Counted(i32, Arc<AtomicUsize>);
+    impl Drop for Counted { fn drop(&mut self) { self.1.fetch_add(1, SeqCst); } }
+    impl PartialEq for Counted { fn eq(&self, rhs: &Counted) -> bool { self.0 == rhs.0 } }
+    impl Eq for Counted { }
+    impl PartialOrd for Counted { fn partial_cmp(&self, rhs: &Counted) -> Option<cmp::Ordering> { Some(Ord::cmp(self, rhs)) } }
+    impl Ord for Counted { fn cmp(&self, rhs: &Counted) -> cmp::Ordering { Ord::cmp(&self.0, &rhs.0) } }
+
+    const THREADS: i32 = 8;
+    const ELEMS: i32 = 2_000;
+    let drops = Arc::new(AtomicUsize::new(0));
+    let mut list: SkipList<Counted> = SkipList::new();
+
+    // Every thread inserts every element, removes the odd ones, and looks up
+    // elements which other threads may be inserting or removing.
+    std::thread::scope(|scope| {
+        for thread in 0..THREADS {
+            let (list, drops) = (&list, drops.clone());
+            scope.spawn(move || for x in 0..ELEMS {
+                let x = (x + thread * ELEMS / THREADS) % ELEMS;
+                list.insert(Counted(x, drops.clone()));
+                if x % 2 == 1 {
+                    list.remove(&Counted(x, drops.clone()));
+                }
+                if let Some(found) = list.get(&Counted((x + 1) % ELEMS, drops.clone())) {
+                    assert_eq!(found.0, (x + 1) % ELEMS);
+                }
+            });
+        }
+    });
+
+    list.check_invariants();
+    assert!(list.elems().map(|x| x.0).eq((0..ELEMS).step_by(2)));
+    assert_eq!(list.len(), (ELEMS / 2) as usize);
+
+    // Once the list is cleared, every element created has been dropped exactly
+    // once, including the removed elements, which were retired.
+    list.clear();
+    let created = THREADS as usize * ELEMS as usize * 5 / 2;
+    assert_eq!(drops.load(SeqCst), created);
+}
+
+#[test]
+fn test_insert_and_ref() {
+    let list: SkipList<i32> = SkipList::new();
+    let inserted = list.insert_and_ref(1).unwrap();
+    assert!(ptr::eq(inserted, list.get(&1).unwrap()));
+    let (rejected, existing) = list.insert_and_ref(1).unwrap_err();
+    assert_eq!(rejected, 1);
+    assert!(ptr::eq(existing, inserted));
+}
+
+#[test]
+fn test_insert_ref() {
+    let list: SkipList<i32> = SkipList::new();
+    assert_eq!(list.insert_ref(1), (&1, true));
+    let (elem, inserted) = list.insert_ref(1);
+    assert!(!inserted);
+    assert!(ptr::eq(elem, list.get(&1).unwrap()));
+    assert_eq!(list.len(), 1);
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn test_unwind_safe() {
+    use std::panic::{self, AssertUnwindSafe};
+    use std::rc::Rc;
+    use std::sync::Arc;
+    use crate::Set;
+
+    let set: Arc<Set<i32>> = Arc::new((0..10).collect());
+    let shared = set.clone();
+    assert_eq!(panic::catch_unwind(|| shared.contains(&3)).ok(), Some(true));
+
+    // A predicate which panics partway through leaves the list relinked, with
+    // the element it panicked on still in it, and drops each element once.
+    let elems: Vec<Rc<i32>> = (0..10).map(Rc::new).collect();
+    let mut list: SkipList<Rc<i32>> = elems.iter().cloned().collect();
+    let result = panic::catch_unwind(AssertUnwindSafe(|| list.retain(|x| {
+        assert!(**x != 5);
+        **x % 2 == 0
+    })));
+    assert!(result.is_err());
+    assert!(list.elems().map(|x| **x).eq([0, 2, 4, 5, 6, 7, 8, 9]));
+    assert_eq!(list.len(), 8);
+    assert_eq!(list.get(&elems[8]), Some(&elems[8]));
+    let counts: Vec<usize> = elems.iter().map(Rc::strong_count).collect();
+    assert_eq!(counts, [2, 1, 2, 1, 2, 2, 2, 2, 2, 2]);
+    drop(list);
+    assert!(elems.iter().all(|x| Rc::strong_count(x) == 1));
+}
@@ -0,0 +1,113 @@
+use core::ptr::{self, NonNull};
+use core::sync::atomic::Ordering::{Acquire, AcqRel, Relaxed};
+
+use crate::AbstractOrd;
+use super::{Allocator, Node, SkipList, marked, with_mark};
+use super::insert::{search, Spot};
+
+// Removal is done in two phases, as in Harris's lock-free linked list. First,
+// the node is logically removed by marking the pointers in each of its lanes,
+// from the highest lane to the lowest. Whichever thread marks the lowest lane
+// has removed the element. Marked pointers are never the target of a
+// successful compare and swap, so no node can be inserted after a removed
+// node. Then, the node is physically unlinked from each lane by pointing its
+// predecessors past it; this is done by search, which unlinks any marked node
+// it comes across, whether it is removing that node or not.
+//
+// Removed nodes are never deallocated while the list is shared, because other
+// threads may still be reading them. Instead, the thread which removes a node
+// retires it, and the retired nodes are freed when the list is next emptied or
+// relinked with exclusive access (by clear, retain, drain, append, split_off,
+// or dropping it). This is why get can return a plain reference rather than a
+// guard which defers reclamation, as epoch-based schemes require: every
+// reference borrows the list, so the node outlives it. The cost is that the
+// memory of removed elements accumulates for as long as the list is shared.
+pub(super) fn remove<'a, T, U, const H: usize, A>(list: &'a SkipList<T, H, A>, elem: &U) -> Option<&'a T>
+where U: AbstractOrd<T> + ?Sized, A: Allocator
+{
+    let node = unlink(list, elem)?;
+    list.retired.push(NonNull::from(node), &list.alloc);
+    Some(&node.inner.elem)
+}
+
+// Marks the node equal to `elem` and unlinks it from every lane, returning it
+// if this thread removed it. The caller is responsible for the node: either it
+// has exclusive access and frees the node itself, or it retires the node.
+pub(super) fn unlink<'a, T, U, const H: usize, A>(list: &'a SkipList<T, H, A>, elem: &U) -> Option<&'a Node<T>>
+where U: AbstractOrd<T> + ?Sized, A: Allocator
+{
+    let mut spots: [Spot<T>; H] = [(ptr::null(), ptr::null_mut()); H];
+    let node: &'a Node<T> = search(list.lanes(), elem, &mut spots)?;
+
+    let (lowest, higher) = node.lanes().split_last().unwrap();
+
+    for lane in higher {
+        let mut next = lane.load(Acquire);
+        while !marked(next) {
+            match lane.compare_exchange_weak(next, with_mark(next), AcqRel, Acquire) {
+                Ok(_)       => break,
+                Err(actual) => next = actual,
+            }
+        }
+    }
+
+    let mut next = lowest.load(Acquire);
+    loop {
+        // Another thread marked the lowest lane first, so it removed the
+        // element instead of us.
+        if marked(next) { return None; }
+
+        match lowest.compare_exchange_weak(next, with_mark(next), AcqRel, Acquire) {
+            Ok(_)       => break,
+            Err(actual) => next = actual,
+        }
+    }
+
+    list.len.fetch_sub(1, Relaxed);
+
+    // Search again, unlinking the node from every lane it is in, beginning
+    // high enough to reach them all. If it was one of the tallest nodes, the
+    // top lanes of the list may now be empty.
+    search(list.lanes_for(node.height()), elem, &mut spots);
+    if node.inner.height >= list.current_height.load(Relaxed) {
+        list.shrink_height();
+    }
+
+    Some(node)
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn test_reference_outlives_remove() {
+    let list: SkipList<String> = ["a", "b", "c"].iter().map(|s| s.to_string()).collect();
+    let key = String::from("b");
+    let b = list.get(&key).unwrap();
+    std::thread::scope(|scope| {
+        scope.spawn(|| assert_eq!(list.remove(&key), Some(&key)));
+    });
+    assert!(!list.contains(&key));
+    assert_eq!(b, "b");
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn test_removed_freed_on_drop() {
+    use std::sync::atomic::AtomicUsize;
+
+    static DROPS: AtomicUsize = AtomicUsize::new(0);
+    #[derive(Ord, PartialOrd, Eq, PartialEq)] struct DropInt(i32);
+    impl Drop for DropInt { fn drop(&mut self) {
+        DROPS.fetch_add(1, Relaxed);
+    } }
+
+    let list: SkipList<DropInt> = (0..100).map(DropInt).collect();
+    let keys: std::vec::Vec<DropInt> = (0..100).step_by(3).map(DropInt).collect();
+    std::thread::scope(|scope| {
+        scope.spawn(|| for key in &keys[..keys.len() / 2] { list.remove(key); });
+        scope.spawn(|| for key in &keys[keys.len() / 2..] { list.remove(key); });
+    });
+    assert_eq!(DROPS.load(Relaxed), 0);
+    assert_eq!(list.len(), 66);
+    drop(list);
+    assert_eq!(DROPS.load(Relaxed), 100);
+}
@@ -0,0 +1,62 @@
+use alloc::alloc::{handle_alloc_error, Layout};
+use core::ptr::{self, NonNull};
+use core::sync::atomic::Ordering::{Acquire, Release, Relaxed};
+
+use crate::sync::AtomicPtr;
+use super::{Allocator, Node};
+
+// The nodes which have been removed while the list was shared. Other threads
+// may still be reading them, so they cannot be freed until the list is next
+// held exclusively; until then, the thread which removed each node pushes it
+// onto this stack, so that it is not lost once it has been unlinked.
+//
+// The stack only ever grows while the list is shared, so pushing needs no
+// protection against ABA. Each entry is a separate allocation from the list's
+// allocator, because the lanes of the removed node are still being read.
+pub(super) struct Retired<T> {
+    head: AtomicPtr<Entry<T>>,
+}
+
+struct Entry<T> {
+    node: NonNull<Node<T>>,
+    next: *mut Entry<T>,
+}
+
+impl<T> Retired<T> {
+    pub(super) fn new() -> Retired<T> {
+        Retired { head: AtomicPtr::default() }
+    }
+
+    pub(super) fn push<A: Allocator>(&self, node: NonNull<Node<T>>, alloc: &A) {
+        let layout = Layout::new::<Entry<T>>();
+        let entry = match alloc.allocate_zeroed(layout) {
+            Some(ptr)   => ptr.as_ptr() as *mut Entry<T>,
+            None        => handle_alloc_error(layout),
+        };
+
+        let mut next = self.head.load(Relaxed);
+        loop {
+            unsafe { ptr::write(entry, Entry { node, next }) }
+            match self.head.compare_exchange_weak(next, entry, Release, Relaxed) {
+                Ok(_)       => return,
+                Err(actual) => next = actual,
+            }
+        }
+    }
+
+    pub(super) fn is_empty(&self) -> bool {
+        self.head.load(Relaxed).is_null()
+    }
+
+    // Safety: none of the retired nodes may be reachable from the list any
+    // longer, and they must have been allocated by `alloc`.
+    pub(super) unsafe fn free<A: Allocator>(&mut self, alloc: &A) {
+        let mut entry = self.head.swap(ptr::null_mut(), Acquire);
+        while let Some(ptr) = NonNull::new(entry) {
+            let Entry { node, next } = ptr::read(ptr.as_ptr());
+            drop((*node.as_ptr()).dealloc(alloc));
+            alloc.deallocate(ptr.cast(), Layout::new::<Entry<T>>());
+            entry = next;
+        }
+    }
+}
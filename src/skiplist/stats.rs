@@ -0,0 +1,80 @@
+#[cfg(feature = "metrics")]
+use core::sync::atomic::Ordering::Relaxed;
+
+#[cfg(feature = "metrics")]
+use crate::sync::AtomicU64;
+
+// Counts of how often inserts lost races to other threads, for tuning lists
+// under contention. A retry is a whole insert starting over after failing to
+// link its node into the lowest lane; a failed exchange is any failure to link
+// the node into a lane, including the higher lanes, which are not retried.
+#[cfg(feature = "metrics")]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SkipListStats {
+    pub retries: u64,
+    pub max_retries: u64,
+    pub cas_failures: u64,
+}
+
+// The counters are only kept with the `metrics` feature. Without it, this is
+// empty and recording does nothing, so inserts pay nothing for it.
+#[derive(Default)]
+pub(super) struct Stats {
+    #[cfg(feature = "metrics")]
+    retries: AtomicU64,
+    #[cfg(feature = "metrics")]
+    max_retries: AtomicU64,
+    #[cfg(feature = "metrics")]
+    cas_failures: AtomicU64,
+}
+
+impl Stats {
+    #[inline]
+    pub(super) fn cas_failed(&self) {
+        #[cfg(feature = "metrics")]
+        self.cas_failures.fetch_add(1, Relaxed);
+    }
+
+    // Record the number of times an insert retried, once it has finished.
+    #[inline]
+    pub(super) fn retried(&self, _retries: u64) {
+        #[cfg(feature = "metrics")]
+        if _retries > 0 {
+            self.retries.fetch_add(_retries, Relaxed);
+            self.max_retries.fetch_max(_retries, Relaxed);
+        }
+    }
+
+    #[cfg(feature = "metrics")]
+    pub(super) fn get(&self) -> SkipListStats {
+        SkipListStats {
+            retries: self.retries.load(Relaxed),
+            max_retries: self.max_retries.load(Relaxed),
+            cas_failures: self.cas_failures.load(Relaxed),
+        }
+    }
+}
+
+#[test]
+#[cfg(feature = "metrics")]
+fn test_stats() {
+    use std::sync::Arc;
+    use std::thread;
+    use super::SkipList;
+
+    let list: Arc<SkipList<i32>> = Arc::new(SkipList::new());
+    let handles: Vec<_> = (0..4).map(|_| {
+        let list = list.clone();
+        thread::spawn(move || for x in 0..1000 { list.insert(x); })
+    }).collect();
+    for handle in handles {
+        handle.join().unwrap();
+    }
+
+    // How often the threads collide depends on how they are scheduled, but
+    // every retry follows a failed exchange.
+    let stats = list.stats();
+    assert!(stats.max_retries <= stats.retries);
+    assert!(stats.retries <= stats.cas_failures);
+    assert_eq!(list.len(), 1000);
+}
@@ -0,0 +1,47 @@
+// The atomics used by the data structures in this crate. When built with
+// `--cfg loom` these are loom's instrumented atomics instead, so that the
+// lock-free algorithms can be model checked:
+//
+//     RUSTFLAGS="--cfg loom" cargo test --release loom
+#[cfg(not(loom))]
+pub(crate) use core::sync::atomic::{AtomicPtr, AtomicU8, AtomicUsize};
+#[cfg(all(not(loom), target_has_atomic = "64"))]
+pub(crate) use core::sync::atomic::AtomicU64;
+#[cfg(not(loom))]
+pub(crate) use core::hint::spin_loop;
+
+#[cfg(loom)]
+pub(crate) use loom::sync::atomic::{AtomicPtr, AtomicU8, AtomicU64, AtomicUsize};
+#[cfg(loom)]
+pub(crate) use loom::hint::spin_loop;
+
+// Loom's atomics have no get_mut, so code with exclusive access to an atomic
+// sets it through this trait, which under loom is just a relaxed store.
+pub(crate) trait AtomicMut {
+    type Value;
+    fn set_mut(&mut self, value: Self::Value);
+}
+
+macro_rules! atomic_mut {
+    ($(impl$(<$T:ident>)? for $atomic:ty => $value:ty;)*) => {$(
+        impl$(<$T>)? AtomicMut for $atomic {
+            type Value = $value;
+
+            #[cfg(not(loom))]
+            fn set_mut(&mut self, value: $value) {
+                *self.get_mut() = value;
+            }
+
+            #[cfg(loom)]
+            fn set_mut(&mut self, value: $value) {
+                self.store(value, core::sync::atomic::Ordering::Relaxed);
+            }
+        }
+    )*}
+}
+
+atomic_mut! {
+    impl<T> for AtomicPtr<T> => *mut T;
+    impl for AtomicU8 => u8;
+    impl for AtomicUsize => usize;
+}